@@ -0,0 +1,489 @@
+//! Reading files from inside tar and zip archives
+//!
+//! FILE arguments which look like archives (or any file given with `--archive`) are expanded to
+//! every member, and `ARCHIVE:MEMBER` arguments select a single file or directory within the
+//! archive. Tar archives may be compressed in any format supported by [`crate::decompress`], and
+//! zip members may be stored or deflated.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::decompress::Format;
+use crate::inflate;
+
+/// Filename extensions recognized as archives in FILE arguments
+const EXTENSIONS: [&str; 10] = [
+    ".tar", ".tar.gz", ".tgz", ".tar.xz", ".txz", ".tar.zst", ".tzst", ".tar.bz2", ".tbz2", ".zip",
+];
+
+/// A regular file inside an archive
+#[derive(Debug)]
+pub struct Member {
+    pub path: String,
+    /// The member's contents, or why they couldn't be extracted
+    pub data: io::Result<Vec<u8>>,
+}
+
+/// Whether a FILE argument names an archive to expand.
+pub fn is_archive(path: &str) -> bool {
+    EXTENSIONS.iter().any(|ext| path.ends_with(ext)) && Path::new(path).is_file()
+}
+
+/// Split an `ARCHIVE:MEMBER` argument, if the part before a `:` names an existing archive. Paths
+/// with colons in them are common in /sys, so anything that exists as-is is left alone.
+pub fn split_member(arg: &str) -> Option<(&str, &str)> {
+    if Path::new(arg).exists() {
+        return None;
+    }
+    arg.match_indices(':').find_map(|(idx, _)| {
+        let (archive, member) = (&arg[..idx], &arg[idx + 1..]);
+        is_archive(archive).then_some((archive, member))
+    })
+}
+
+/// Read the regular files from an archive. If `prefix` is given, only return the member with that
+/// path, or the members in that directory.
+pub fn read(path: impl AsRef<Path>, prefix: Option<&str>) -> io::Result<Vec<Member>> {
+    let mut data = fs::read(path)?;
+    let mut members = if data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06") {
+        read_zip(&data)?
+    } else {
+        if let Some(format) = Format::detect(&data) {
+            data = format.decompress(&data, None)?;
+        }
+        read_tar(&data)?
+    };
+
+    if let Some(prefix) = prefix.map(|p| p.trim_start_matches("./").trim_end_matches('/')) {
+        members.retain(|m| {
+            m.path == prefix
+                || prefix.is_empty()
+                || m.path
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        });
+        if members.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("'{prefix}' not found in archive"),
+            ));
+        }
+    }
+    Ok(members)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_owned())
+}
+
+/// Get a NUL-terminated string field from a tar header.
+fn field(block: &[u8]) -> String {
+    let end = block.iter().position(|b| *b == 0).unwrap_or(block.len());
+    String::from_utf8_lossy(&block[..end]).into_owned()
+}
+
+/// Parse a numeric tar header field, which is octal text or, for large values, big-endian binary
+/// flagged by the high bit.
+fn number(block: &[u8]) -> io::Result<u64> {
+    if block.first().is_some_and(|b| b & 0x80 != 0) {
+        return block[1..]
+            .iter()
+            .try_fold(0u64, |n, b| n.checked_mul(256).map(|n| n | *b as u64))
+            .ok_or_else(|| invalid("number too large in tar header"));
+    }
+    let text = field(block);
+    let text = text.trim_matches(|c: char| c == ' ' || c == '\0');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8).map_err(|_| invalid("invalid number in tar header"))
+}
+
+/// Get the `path` record from pax extended header data.
+fn pax_path(data: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(data);
+    text.lines().find_map(|record| {
+        // records are "LENGTH KEY=VALUE"
+        let (_, kv) = record.split_once(' ')?;
+        kv.strip_prefix("path=").map(str::to_owned)
+    })
+}
+
+fn read_tar(data: &[u8]) -> io::Result<Vec<Member>> {
+    const BLOCK: usize = 512;
+
+    if !data.is_empty() && data.len() < BLOCK {
+        return Err(invalid("not a tar archive"));
+    }
+
+    let mut members = Vec::new();
+    let mut pos = 0;
+    // name overrides from GNU long name and pax headers, for the following entry
+    let mut long_name: Option<String> = None;
+
+    while pos + BLOCK <= data.len() {
+        let header = &data[pos..pos + BLOCK];
+        if header.iter().all(|b| *b == 0) {
+            break;
+        }
+        // the checksum is computed with the checksum field itself set to spaces
+        let sum: u64 = header
+            .iter()
+            .enumerate()
+            .map(|(i, b)| if (148..156).contains(&i) { b' ' } else { *b } as u64)
+            .sum();
+        if number(&header[148..156])? != sum {
+            return Err(invalid("not a tar archive"));
+        }
+
+        // sizes come from the archive, so they can be anything
+        let truncated = || invalid("truncated tar archive");
+        let size = usize::try_from(number(&header[124..136])?).map_err(|_| truncated())?;
+        let start = pos + BLOCK;
+        let end = start.checked_add(size).ok_or_else(truncated)?;
+        let body = data.get(start..end).ok_or_else(truncated)?;
+        pos = size
+            .div_ceil(BLOCK)
+            .checked_mul(BLOCK)
+            .and_then(|padded| start.checked_add(padded))
+            .ok_or_else(truncated)?;
+
+        // POSIX ustar headers have a path prefix field, which old GNU headers use for other things
+        let mut name = field(&header[..100]);
+        let prefix = field(&header[345..500]);
+        if &header[257..263] == b"ustar\0" && !prefix.is_empty() {
+            name = format!("{prefix}/{name}");
+        }
+
+        match header[156] {
+            b'0' | b'\0' | b'7' => {
+                let name = long_name.take().unwrap_or(name);
+                members.push(Member {
+                    path: name.trim_start_matches("./").to_owned(),
+                    data: Ok(body.to_vec()),
+                });
+            }
+            b'L' => long_name = Some(field(body)),
+            b'x' => long_name = pax_path(body).or(long_name),
+            // directories, links, and other special entries
+            _ => long_name = None,
+        }
+    }
+    Ok(members)
+}
+
+fn read_zip(data: &[u8]) -> io::Result<Vec<Member>> {
+    const EOCD_SIG: &[u8] = b"PK\x05\x06";
+    const CDIR_SIG: &[u8] = b"PK\x01\x02";
+    const LOCAL_SIG: &[u8] = b"PK\x03\x04";
+
+    let u16_at = |pos: usize| -> io::Result<usize> {
+        let b = data
+            .get(pos..pos + 2)
+            .ok_or_else(|| invalid("truncated zip archive"))?;
+        Ok(u16::from_le_bytes([b[0], b[1]]) as usize)
+    };
+    let u32_at = |pos: usize| -> io::Result<usize> {
+        let b = data
+            .get(pos..pos + 4)
+            .ok_or_else(|| invalid("truncated zip archive"))?;
+        Ok(u32::from_le_bytes(b.try_into().unwrap()) as usize)
+    };
+
+    // the end of central directory record is at the end, followed by a comment of up to 64 KiB
+    let eocd = (0..=data.len().saturating_sub(22))
+        .rev()
+        .take(22 + 0xffff)
+        .find(|&pos| data[pos..].starts_with(EOCD_SIG))
+        .ok_or_else(|| invalid("not a zip archive"))?;
+    let count = u16_at(eocd + 10)?;
+    let mut pos = u32_at(eocd + 16)?;
+    if count == 0xffff || pos == 0xffff_ffff {
+        return Err(invalid("zip64 archives aren't supported"));
+    }
+
+    let mut members = Vec::with_capacity(count);
+    for _ in 0..count {
+        if !data[pos.min(data.len())..].starts_with(CDIR_SIG) {
+            return Err(invalid("corrupt zip central directory"));
+        }
+        let flags = u16_at(pos + 8)?;
+        let method = u16_at(pos + 10)?;
+        let crc = u32_at(pos + 16)? as u32;
+        let csize = u32_at(pos + 20)?;
+        let usize_ = u32_at(pos + 24)?;
+        let (nlen, xlen, clen) = (u16_at(pos + 28)?, u16_at(pos + 30)?, u16_at(pos + 32)?);
+        let local = u32_at(pos + 42)?;
+        let name = data
+            .get(pos + 46..pos + 46 + nlen)
+            .ok_or_else(|| invalid("truncated zip archive"))?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        pos += 46 + nlen + xlen + clen;
+
+        if name.ends_with('/') {
+            continue;
+        }
+
+        let extract = || -> io::Result<Vec<u8>> {
+            if flags & 1 != 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "member is encrypted",
+                ));
+            }
+            if !data[local.min(data.len())..].starts_with(LOCAL_SIG) {
+                return Err(invalid("corrupt zip local header"));
+            }
+            let start = local + 30 + u16_at(local + 26)? + u16_at(local + 28)?;
+            let raw = data
+                .get(start..start + csize)
+                .ok_or_else(|| invalid("truncated zip archive"))?;
+            let out = match method {
+                0 => raw.to_vec(),
+                8 => inflate::inflate(raw, None)?.0,
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        format!("unsupported zip compression method {method}"),
+                    ))
+                }
+            };
+            if out.len() != usize_ || inflate::crc32(&out) != crc {
+                return Err(invalid("zip checksum mismatch"));
+            }
+            Ok(out)
+        };
+        members.push(Member {
+            path: name,
+            data: extract(),
+        });
+    }
+    Ok(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tar entry: a ustar header followed by the padded body.
+    fn tar_entry(name: &str, kind: u8, body: &[u8]) -> Vec<u8> {
+        let mut header = vec![0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..108].copy_from_slice(b"0000644\0");
+        header[124..136].copy_from_slice(format!("{:011o}\0", body.len()).as_bytes());
+        header[136..148].copy_from_slice(b"00000000000\0");
+        header[156] = kind;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        set_checksum(&mut header);
+        header.extend(body);
+        header.resize(header.len().div_ceil(512) * 512, 0);
+        header
+    }
+
+    fn set_checksum(header: &mut [u8]) {
+        header[148..156].fill(b' ');
+        let sum: u32 = header[..512].iter().map(|b| *b as u32).sum();
+        header[148..156].copy_from_slice(format!("{sum:06o}\0 ").as_bytes());
+    }
+
+    fn tar(entries: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = entries.concat();
+        data.extend([0; 1024]);
+        data
+    }
+
+    fn paths(members: &[Member]) -> Vec<&str> {
+        members.iter().map(|m| m.path.as_str()).collect()
+    }
+
+    #[test]
+    fn tar_members() {
+        let data = tar(&[
+            tar_entry("dir/", b'5', b""),
+            tar_entry("./dir/a", b'0', b"alpha\n"),
+            tar_entry("dir/link", b'2', b""),
+            tar_entry("b", b'\0', &[b'x'; 600]),
+        ]);
+        let members = read_tar(&data).unwrap();
+        assert_eq!(paths(&members), ["dir/a", "b"]);
+        assert_eq!(members[0].data.as_ref().unwrap(), b"alpha\n");
+        assert_eq!(members[1].data.as_ref().unwrap().len(), 600);
+        assert!(read_tar(b"").unwrap().is_empty());
+    }
+
+    #[test]
+    fn tar_long_names() {
+        let long = "d/".repeat(80) + "file";
+        let mut prefixed = tar_entry("name", b'0', b"3");
+        prefixed[345..351].copy_from_slice(b"prefix");
+        set_checksum(&mut prefixed);
+        let data = tar(&[
+            tar_entry("././@LongLink", b'L', format!("{long}\0").as_bytes()),
+            tar_entry("truncated", b'0', b"1"),
+            tar_entry("pax", b'x', b"30 mtime=1700000000.5\n17 path=pax/b\n"),
+            tar_entry("short", b'0', b"2"),
+            prefixed,
+            tar_entry("plain", b'0', b"4"),
+        ]);
+        let members = read_tar(&data).unwrap();
+        assert_eq!(
+            paths(&members),
+            [long.as_str(), "pax/b", "prefix/name", "plain"]
+        );
+    }
+
+    #[test]
+    fn bad_tars() {
+        assert!(read_tar(b"not a tar archive").is_err());
+        let mut bad_sum = tar(&[tar_entry("a", b'0', b"alpha")]);
+        bad_sum[0] = b'b';
+        assert!(read_tar(&bad_sum).is_err());
+
+        let entry = tar_entry("a", b'0', &[b'x'; 1000]);
+        assert!(read_tar(&entry[..700]).is_err());
+
+        // a binary size field which doesn't fit in 64 bits
+        let mut huge = tar_entry("a", b'0', b"");
+        huge[124] = 0x80;
+        huge[125..136].fill(0xff);
+        set_checksum(&mut huge);
+        assert!(read_tar(&tar(&[huge.clone()])).is_err());
+        // and one which fits, but is far larger than the archive
+        huge[125..128].fill(0);
+        set_checksum(&mut huge);
+        assert!(read_tar(&tar(&[huge])).is_err());
+    }
+
+    #[test]
+    fn tar_numbers() {
+        assert_eq!(number(b"00000000644\0").unwrap(), 0o644);
+        assert_eq!(number(b" 644 \0").unwrap(), 0o644);
+        assert_eq!(number(b"\0\0\0\0").unwrap(), 0);
+        assert_eq!(number(b"\x80\0\0\x01\x00").unwrap(), 256);
+        assert!(number(b"12389\0").is_err());
+    }
+
+    /// A zip member: name, compression method, flags, stored data, and uncompressed data.
+    type ZipEntry<'a> = (&'a str, u16, u16, &'a [u8], &'a [u8]);
+
+    fn zip(entries: &[ZipEntry]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut cdir = Vec::new();
+        for (name, method, flags, raw, plain) in entries {
+            let mut common = Vec::new();
+            common.extend(flags.to_le_bytes());
+            common.extend(method.to_le_bytes());
+            common.extend([0; 4]);
+            common.extend(inflate::crc32(plain).to_le_bytes());
+            common.extend((raw.len() as u32).to_le_bytes());
+            common.extend((plain.len() as u32).to_le_bytes());
+            common.extend((name.len() as u16).to_le_bytes());
+            common.extend([0; 2]);
+
+            cdir.extend(b"PK\x01\x02\x14\x00\x14\x00");
+            cdir.extend(&common);
+            cdir.extend([0; 10]);
+            cdir.extend((data.len() as u32).to_le_bytes());
+            cdir.extend(name.as_bytes());
+
+            data.extend(b"PK\x03\x04\x14\x00");
+            data.extend(&common);
+            data.extend(name.as_bytes());
+            data.extend(*raw);
+        }
+        let offset = data.len() as u32;
+        let count = (entries.len() as u16).to_le_bytes();
+        data.extend(&cdir);
+        data.extend(b"PK\x05\x06\0\0\0\0");
+        data.extend(count);
+        data.extend(count);
+        data.extend((cdir.len() as u32).to_le_bytes());
+        data.extend(offset.to_le_bytes());
+        data.extend(b"\0\0");
+        data
+    }
+
+    const HELLO: &[u8] = b"hello hello hello\n";
+    const HELLO_DEFLATED: &[u8] = b"\xcb\x48\xcd\xc9\xc9\x57\xc8\x40\x90\x5c\x00";
+
+    #[test]
+    fn zip_members() {
+        let data = zip(&[
+            ("dir/", 0, 0, b"", b""),
+            ("dir/stored", 0, 0, b"alpha\n", b"alpha\n"),
+            ("deflated", 8, 0, HELLO_DEFLATED, HELLO),
+        ]);
+        let members = read_zip(&data).unwrap();
+        assert_eq!(paths(&members), ["dir/stored", "deflated"]);
+        assert_eq!(members[0].data.as_ref().unwrap(), b"alpha\n");
+        assert_eq!(members[1].data.as_ref().unwrap(), HELLO);
+        assert!(read_zip(&zip(&[])).unwrap().is_empty());
+    }
+
+    #[test]
+    fn bad_zip_members() {
+        let data = zip(&[
+            ("encrypted", 0, 1, b"alpha", b"alpha"),
+            ("bzip2", 12, 0, b"alpha", b"alpha"),
+            ("checksum", 0, 0, b"alpha", b"beta!"),
+            ("good", 0, 0, b"alpha", b"alpha"),
+        ]);
+        let members = read_zip(&data).unwrap();
+        let err = |i: usize| members[i].data.as_ref().unwrap_err().kind();
+        assert_eq!(err(0), io::ErrorKind::Unsupported);
+        assert_eq!(err(1), io::ErrorKind::Unsupported);
+        assert_eq!(err(2), io::ErrorKind::InvalidData);
+        assert_eq!(members[3].data.as_ref().unwrap(), b"alpha");
+    }
+
+    #[test]
+    fn bad_zips() {
+        assert!(read_zip(b"PK\x03\x04").is_err());
+        let data = zip(&[("a", 0, 0, b"alpha", b"alpha")]);
+        // the central directory is missing
+        assert!(read_zip(&data[data.len() - 22..]).is_err());
+        // the member's data is cut off
+        let mut short = data.clone();
+        short[18..22].copy_from_slice(&1000u32.to_le_bytes());
+        let cdir = data.len() - 22 - 47;
+        short[cdir + 20..cdir + 24].copy_from_slice(&1000u32.to_le_bytes());
+        assert!(read_zip(&short).unwrap()[0].data.is_err());
+    }
+
+    #[test]
+    fn members_by_prefix() {
+        let dir = std::env::temp_dir().join(format!("tabby-archive-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.tar");
+        fs::write(
+            &path,
+            tar(&[
+                tar_entry("dir/a", b'0', b"1"),
+                tar_entry("dir/b", b'0', b"2"),
+                tar_entry("dirt", b'0', b"3"),
+            ]),
+        )
+        .unwrap();
+        let path_str = path.to_str().unwrap();
+
+        assert!(is_archive(path_str));
+        assert!(!is_archive(dir.to_str().unwrap()));
+        let arg = format!("{path_str}:dir/a");
+        assert_eq!(split_member(&arg), Some((path_str, "dir/a")));
+        assert_eq!(split_member(path_str), None);
+
+        let members = |prefix| read(&path, prefix).map(|m| paths(&m).join(" "));
+        assert_eq!(members(None).unwrap(), "dir/a dir/b dirt");
+        assert_eq!(members(Some("dir")).unwrap(), "dir/a dir/b");
+        assert_eq!(members(Some("./dir/")).unwrap(), "dir/a dir/b");
+        assert_eq!(members(Some("dir/b")).unwrap(), "dir/b");
+        assert_eq!(members(Some("")).unwrap(), "dir/a dir/b dirt");
+        assert_eq!(
+            members(Some("di")).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
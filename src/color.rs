@@ -0,0 +1,183 @@
+//! Terminal colors for the text output format
+
+use std::fmt;
+use std::io::{self, IsTerminal};
+
+/// When to use colors, from `--color`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum When {
+    /// Only when writing to a terminal, and `NO_COLOR` isn't set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl When {
+    pub fn enabled(self) -> bool {
+        match self {
+            When::Auto => {
+                io::stdout().is_terminal()
+                    && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                    && std::env::var_os("TERM").is_none_or(|term| term != "dumb")
+            }
+            When::Always => true,
+            When::Never => false,
+        }
+    }
+}
+
+/// A text style, as the parameters of an ANSI SGR escape sequence like `1;31`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Style(String);
+
+impl Style {
+    pub fn new(sgr: &str) -> Style {
+        Style(sgr.to_owned())
+    }
+
+    /// Wrap `text` in this style's escape sequences.
+    pub fn paint<T: fmt::Display>(&self, text: T) -> Painted<'_, T> {
+        Painted { style: self, text }
+    }
+}
+
+/// Text to be displayed in a style
+pub struct Painted<'a, T> {
+    style: &'a Style,
+    text: T,
+}
+
+impl<T: fmt::Display> fmt::Display for Painted<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.style.0.is_empty() {
+            write!(f, "{}", self.text)
+        } else {
+            write!(f, "\x1b[{}m{}\x1b[0m", self.style.0, self.text)
+        }
+    }
+}
+
+/// The styles used for each part of the output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    pub path: Style,
+    /// The contents of one-line files, unless they're one of the kinds below
+    pub value: Style,
+    /// Values which look like numbers
+    pub number: Style,
+    /// Values which look like booleans, like `0`, `Y`, or `off`
+    pub boolean: Style,
+    /// Values which are empty or only whitespace
+    pub empty: Style,
+    /// Placeholders shown instead of contents, like `[fifo]`
+    pub placeholder: Style,
+    /// Multiline file headers
+    pub header: Style,
+    /// Files which couldn't be read
+    pub error: Style,
+    /// Matches of `--highlight`
+    pub matched: Style,
+}
+
+/// The names of the built-in themes, for `--theme`
+pub const THEMES: [&str; 3] = ["dark", "light", "solarized"];
+
+impl Palette {
+    /// No colors at all
+    pub fn plain() -> Palette {
+        Palette {
+            path: Style::default(),
+            value: Style::default(),
+            number: Style::default(),
+            boolean: Style::default(),
+            empty: Style::default(),
+            placeholder: Style::default(),
+            header: Style::default(),
+            error: Style::default(),
+            matched: Style::default(),
+        }
+    }
+
+    /// One of the built-in [`THEMES`]. The dark theme is the default, and the light theme swaps
+    /// out the cyan and yellow that are hard to read on a white background.
+    pub fn theme(name: &str) -> Palette {
+        match name {
+            "light" => Palette {
+                path: Style::new("34"),
+                value: Style::default(),
+                number: Style::new("35"),
+                boolean: Style::new("32"),
+                empty: Style::new("2"),
+                placeholder: Style::new("90"),
+                header: Style::new("1;34"),
+                error: Style::new("31"),
+                matched: Style::new("1;35"),
+            },
+            // the accent colors from the Solarized palette work on both its light and dark
+            // backgrounds
+            "solarized" => Palette {
+                path: Style::new("38;5;37"),
+                value: Style::default(),
+                number: Style::new("38;5;125"),
+                boolean: Style::new("38;5;64"),
+                empty: Style::new("2"),
+                placeholder: Style::new("38;5;245"),
+                header: Style::new("1;38;5;33"),
+                error: Style::new("38;5;160"),
+                matched: Style::new("1;38;5;136"),
+            },
+            _ => Palette::default(),
+        }
+    }
+
+    /// Override styles from a `--colors` list like `path=1;34:error=31`. An empty style turns
+    /// off the color for that part.
+    pub fn set(&mut self, spec: &str) -> Result<(), String> {
+        for item in spec.split(':').filter(|item| !item.is_empty()) {
+            let Some((name, sgr)) = item.split_once('=') else {
+                return Err(format!("expected NAME=STYLE, not '{item}'"));
+            };
+            if !sgr.bytes().all(|b| b.is_ascii_digit() || b == b';') {
+                return Err(format!(
+                    "invalid style '{sgr}', expected SGR parameters like '1;31'"
+                ));
+            }
+            let style = match name {
+                "path" => &mut self.path,
+                "value" => &mut self.value,
+                "number" => &mut self.number,
+                "boolean" => &mut self.boolean,
+                "empty" => &mut self.empty,
+                "placeholder" => &mut self.placeholder,
+                "header" | "banner" => &mut self.header,
+                "error" => &mut self.error,
+                "match" => &mut self.matched,
+                _ => {
+                    return Err(format!(
+                        "unknown color '{name}', expected path, value, number, boolean, empty, \
+                         placeholder, header, error, or match"
+                    ))
+                }
+            };
+            *style = Style::new(sgr);
+        }
+        Ok(())
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette {
+            path: Style::new("36"),
+            value: Style::default(),
+            number: Style::new("35"),
+            boolean: Style::new("32"),
+            empty: Style::new("2"),
+            placeholder: Style::new("90"),
+            header: Style::new("1;36"),
+            error: Style::new("31"),
+            matched: Style::new("1;33"),
+        }
+    }
+}
@@ -0,0 +1,283 @@
+//! Default arguments read from a config file
+//!
+//! The config file is `$TABBY_CONFIG` if set, otherwise `$XDG_CONFIG_HOME/tabby/config` (or
+//! `~/.config/tabby/config`). Each non-blank line that doesn't start with `#` holds an option and
+//! its values, like `--exclude '*.bak'`, split into words like a shell would. Options given on the
+//! command line replace the config file's, including other choices from the same group, so that
+//! `--json` on the command line wins over `--yaml` in the config file.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Command};
+
+fn config_path() -> Option<(PathBuf, bool)> {
+    if let Some(path) = env::var_os("TABBY_CONFIG") {
+        return (!path.is_empty()).then(|| (PathBuf::from(path), true));
+    }
+    let dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some((dir.join("tabby/config"), false))
+}
+
+/// Split a line into words on whitespace, with single or double quotes around words containing
+/// spaces, and backslashes escaping the next character outside of single quotes.
+fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' | '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some('\\') if c == '"' => word.extend(chars.next()),
+                        Some(ch) => word.push(ch),
+                        None => return Err(format!("unclosed {c} quote")),
+                    }
+                }
+            }
+            '\\' => word.get_or_insert_with(String::new).extend(chars.next()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Read the lines of arguments from the config file, if there is one.
+fn file_lines() -> Vec<Vec<String>> {
+    let Some((path, explicit)) = config_path() else {
+        return Vec::new();
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) => {
+            // a missing default config file is normal, but complain about one we were told to use
+            if explicit {
+                eprintln!(
+                    "tabby: unable to read config file {}: {err}",
+                    path.display()
+                );
+            }
+            return Vec::new();
+        }
+    };
+    let mut lines = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match split_words(line) {
+            Ok(words) => lines.push(words),
+            Err(err) => eprintln!("tabby: {}:{}: {err}", path.display(), n + 1),
+        }
+    }
+    lines
+}
+
+/// Parse some words on their own, ignoring any errors.
+fn partial_matches(cmd: &Command, words: &[OsString]) -> Option<ArgMatches> {
+    let prog = OsString::from(cmd.get_name());
+    cmd.clone()
+        .ignore_errors(true)
+        .try_get_matches_from(std::iter::once(&prog).chain(words))
+        .ok()
+}
+
+/// The IDs of the arguments that some words set.
+fn arg_ids(cmd: &Command, m: &ArgMatches) -> Vec<String> {
+    let is_group = |id: &str| cmd.get_groups().any(|group| group.get_id() == id);
+    m.ids()
+        .map(|id| id.as_str().to_owned())
+        .filter(|id| !is_group(id) && m.value_source(id) == Some(ValueSource::CommandLine))
+        .collect()
+}
+
+/// Whether some words give `--sort value`, which sorts the output in place of `--order`.
+fn sorts_by_value(m: &ArgMatches) -> bool {
+    m.value_source("sort") == Some(ValueSource::CommandLine)
+        && m.try_get_one::<String>("sort")
+            .ok()
+            .flatten()
+            .map(String::as_str)
+            == Some("value")
+}
+
+fn sets_order(m: &ArgMatches) -> bool {
+    m.value_source("order") == Some(ValueSource::CommandLine)
+}
+
+/// Whether the command-line arguments give both `--sort value` and `--order`, which clap can't
+/// check because `--sort` is fine with `--order` otherwise.
+pub fn sort_conflicts(cmd: &Command, cli: &[OsString]) -> bool {
+    partial_matches(cmd, cli).is_some_and(|m| sorts_by_value(&m) && sets_order(&m))
+}
+
+/// The command-line arguments, without the program name.
+pub fn cli_args() -> Vec<OsString> {
+    env::args_os().skip(1).collect()
+}
+
+/// Build the full argument list: the program name, then the config file arguments which weren't
+/// replaced on the command line (unless `--no-config` was given), then the command-line arguments.
+pub fn args_with_config(cmd: &Command) -> Vec<OsString> {
+    let prog = env::args_os().next().into_iter();
+    let cli = cli_args();
+    // anything after -- is a filename
+    if cli
+        .iter()
+        .take_while(|arg| *arg != "--")
+        .any(|arg| arg == "--no-config")
+    {
+        return prog.chain(cli).collect();
+    }
+    prog.chain(merge(cmd, file_lines(), cli)).collect()
+}
+
+/// Put the config file lines which aren't replaced by the command line before it.
+fn merge(cmd: &Command, lines: Vec<Vec<String>>, cli: Vec<OsString>) -> Vec<OsString> {
+    let Some(cli_matches) = partial_matches(cmd, &cli) else {
+        return lines
+            .into_iter()
+            .flatten()
+            .map(OsString::from)
+            .chain(cli)
+            .collect();
+    };
+    // only one of an exclusive group's arguments can be given, so the command line's replaces all
+    // of them
+    let mut replaced = arg_ids(cmd, &cli_matches);
+    for group in cmd.get_groups() {
+        let ids: Vec<&str> = group.get_args().map(|id| id.as_str()).collect();
+        if !group.clone().is_multiple() && ids.iter().any(|id| replaced.iter().any(|r| r == id)) {
+            replaced.extend(ids.iter().map(|id| id.to_string()));
+        }
+    }
+
+    let mut args = Vec::new();
+    for line in lines {
+        let words: Vec<OsString> = line.into_iter().map(OsString::from).collect();
+        // --sort value and --order can't be given together, so either replaces the other
+        let is_replaced = partial_matches(cmd, &words).is_some_and(|m| {
+            (sorts_by_value(&m) && sets_order(&cli_matches))
+                || (sets_order(&m) && sorts_by_value(&cli_matches))
+                || arg_ids(cmd, &m).iter().any(|id| replaced.contains(id))
+        });
+        if !is_replaced {
+            args.extend(words);
+        }
+    }
+    args.extend(cli);
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction, ArgGroup};
+
+    fn command() -> Command {
+        Command::new("tabby")
+            .arg(Arg::new("files").action(ArgAction::Append))
+            .arg(Arg::new("sort").long("sort").default_value("name"))
+            .arg(Arg::new("order").long("order").default_value("cli"))
+            .arg(
+                Arg::new("exclude")
+                    .long("exclude")
+                    .action(ArgAction::Append),
+            )
+            .arg(Arg::new("json").long("json").action(ArgAction::SetTrue))
+            .arg(Arg::new("yaml").long("yaml").action(ArgAction::SetTrue))
+            .group(ArgGroup::new("output_format").args(["json", "yaml"]))
+    }
+
+    fn words(line: &str) -> Vec<OsString> {
+        split_words(line)
+            .unwrap()
+            .into_iter()
+            .map(OsString::from)
+            .collect()
+    }
+
+    fn merged(config: &[&str], cli: &str) -> String {
+        let lines = config
+            .iter()
+            .map(|line| split_words(line).unwrap())
+            .collect();
+        let args = merge(&command(), lines, words(cli));
+        args.iter()
+            .map(|arg| arg.to_str().unwrap())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn splits_words() {
+        assert_eq!(
+            split_words("--exclude  '*.bak'").unwrap(),
+            ["--exclude", "*.bak"]
+        );
+        assert_eq!(
+            split_words(r#"a"b c"d 'e\f' g\ h"#).unwrap(),
+            ["ab cd", "e\\f", "g h"]
+        );
+        assert_eq!(split_words(r#""say \"hi\"""#).unwrap(), [r#"say "hi""#]);
+        assert_eq!(split_words("'' x").unwrap(), ["", "x"]);
+        assert!(split_words("'open").is_err());
+        assert!(split_words("\"open").is_err());
+    }
+
+    #[test]
+    fn command_line_replaces_config() {
+        let config = ["--exclude '*.bak'", "--json", "--sort size"];
+        assert_eq!(merged(&config, "a"), "--exclude *.bak --json --sort size a");
+        assert_eq!(
+            merged(&config, "--exclude x a"),
+            "--json --sort size --exclude x a"
+        );
+        // other choices from the same group are replaced too
+        assert_eq!(
+            merged(&config, "--yaml a"),
+            "--exclude *.bak --sort size --yaml a"
+        );
+    }
+
+    #[test]
+    fn sort_value_replaces_order() {
+        assert_eq!(
+            merged(&["--order name"], "--sort value a"),
+            "--sort value a"
+        );
+        assert_eq!(
+            merged(&["--sort value"], "--order name a"),
+            "--order name a"
+        );
+        // other sort keys are fine with --order
+        assert_eq!(
+            merged(&["--sort size"], "--order name a"),
+            "--sort size --order name a"
+        );
+    }
+
+    #[test]
+    fn sort_conflicts_on_command_line() {
+        let cmd = command();
+        assert!(sort_conflicts(&cmd, &words("--sort value --order name a")));
+        assert!(!sort_conflicts(&cmd, &words("--sort value a")));
+        assert!(!sort_conflicts(&cmd, &words("--sort size --order name a")));
+        assert!(!sort_conflicts(
+            &cmd,
+            &words("-- --sort value --order name")
+        ));
+    }
+}
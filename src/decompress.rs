@@ -0,0 +1,188 @@
+//! Transparent decompression of compressed files
+//!
+//! Formats are detected by their magic numbers rather than filename extensions. Gzip is handled
+//! internally, while the others are piped through the `xz`, `zstd`, or `bzip2` programs.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::inflate;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Gzip,
+    Xz,
+    Zstd,
+    Bzip2,
+}
+
+impl Format {
+    /// Detect the compression format from the start of some data.
+    pub fn detect(data: &[u8]) -> Option<Format> {
+        if inflate::is_gzip(data) {
+            Some(Format::Gzip)
+        } else if data.starts_with(b"\xfd7zXZ\0") {
+            Some(Format::Xz)
+        } else if data.starts_with(b"\x28\xb5\x2f\xfd") {
+            Some(Format::Zstd)
+        } else if data.len() >= 4 && data.starts_with(b"BZh") && data[3].is_ascii_digit() {
+            Some(Format::Bzip2)
+        } else {
+            None
+        }
+    }
+
+    /// Detect the compression format of a regular file.
+    pub fn detect_file(path: impl AsRef<Path>) -> Option<Format> {
+        // check before opening, since opening a FIFO blocks until there's a writer
+        if !fs::metadata(&path).ok()?.is_file() {
+            return None;
+        }
+        let file = File::open(path).ok()?;
+        let mut magic = Vec::with_capacity(6);
+        file.take(6).read_to_end(&mut magic).ok()?;
+        Format::detect(&magic)
+    }
+
+    /// Decompress `data`, stopping once the output reaches `limit` bytes if it's given.
+    pub fn decompress(self, data: &[u8], limit: Option<u64>) -> io::Result<Vec<u8>> {
+        match self {
+            Format::Gzip => inflate::gunzip(data, limit),
+            Format::Xz => run_filter("xz", data, limit),
+            Format::Zstd => run_filter("zstd", data, limit),
+            Format::Bzip2 => run_filter("bzip2", data, limit),
+        }
+    }
+
+    /// Decompress a file, reading no more of it than could be needed for `limit` bytes of output.
+    pub fn decompress_file(
+        self,
+        path: impl AsRef<Path>,
+        limit: Option<u64>,
+    ) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut file = File::open(path)?;
+        match limit {
+            // none of the formats take more than two bytes per byte of output, aside from headers
+            Some(limit) => {
+                let most = limit.saturating_mul(2).saturating_add(64 * 1024);
+                file.take(most).read_to_end(&mut data)?
+            }
+            None => file.read_to_end(&mut data)?,
+        };
+        self.decompress(&data, limit)
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Format::Gzip => "gzip",
+            Format::Xz => "xz",
+            Format::Zstd => "zstd",
+            Format::Bzip2 => "bzip2",
+        })
+    }
+}
+
+/// Decompress `data` with `prog -dc`, killing it once it's written `limit` bytes.
+fn run_filter(prog: &str, data: &[u8], limit: Option<u64>) -> io::Result<Vec<u8>> {
+    let mut child = Command::new(prog)
+        .arg("-dc")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => io::Error::new(
+                err.kind(),
+                format!("{prog} is required to decompress this file"),
+            ),
+            _ => err,
+        })?;
+
+    // feed stdin and drain stderr from other threads so that no pipe can fill up and deadlock
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+    let mut stderr = child.stderr.take().unwrap();
+    let (out, status, errors) = thread::scope(|s| {
+        // the write fails once the child is killed, which is fine
+        s.spawn(move || stdin.write_all(data));
+        let errors = s.spawn(move || {
+            let mut errors = Vec::new();
+            stderr.read_to_end(&mut errors).map(|_| errors)
+        });
+        let mut out = Vec::new();
+        let read = match limit {
+            Some(limit) => (&mut stdout).take(limit).read_to_end(&mut out),
+            None => stdout.read_to_end(&mut out),
+        };
+        if limit.is_some_and(|limit| out.len() as u64 >= limit) {
+            let _ = child.kill();
+        }
+        drop(stdout);
+        let status = child.wait();
+        (read.map(|_| out), status, errors.join().unwrap())
+    });
+    let out = out?;
+    if limit.is_some_and(|limit| out.len() as u64 >= limit) {
+        return Ok(out);
+    }
+    if status?.success() {
+        Ok(out)
+    } else {
+        let errors = errors?;
+        let msg = String::from_utf8_lossy(&errors);
+        let msg = msg
+            .trim()
+            .trim_start_matches(&format!("{prog}: "))
+            .to_owned();
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            if msg.is_empty() {
+                format!("{prog} failed")
+            } else {
+                msg
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_formats() {
+        let gz =
+            b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\x03\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+        assert_eq!(Format::detect(gz), Some(Format::Gzip));
+        assert_eq!(Format::detect(b"\xfd7zXZ\0\0\x04"), Some(Format::Xz));
+        assert_eq!(Format::detect(b"\x28\xb5\x2f\xfd\x24"), Some(Format::Zstd));
+        assert_eq!(Format::detect(b"BZh91AY&SY"), Some(Format::Bzip2));
+        assert_eq!(Format::detect(b"BZhx"), None);
+        assert_eq!(Format::detect(b"\xfd7zX"), None);
+        assert_eq!(Format::detect(b"plain text"), None);
+        assert_eq!(Format::detect(b""), None);
+    }
+
+    #[test]
+    fn gzip_limit() {
+        let gz = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\xcb\xc8\xe4\x02\x00\x7a\x7a\x6f\xed\x03\x00\x00\x00";
+        assert_eq!(Format::Gzip.decompress(gz, None).unwrap(), b"hi\n");
+        assert_eq!(Format::Gzip.decompress(gz, Some(1)).unwrap(), b"h");
+    }
+
+    #[test]
+    fn missing_program() {
+        let err = run_filter("tabby-no-such-program", b"", None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err
+            .to_string()
+            .contains("tabby-no-such-program is required"));
+    }
+}
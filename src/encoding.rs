@@ -0,0 +1,366 @@
+//! Decoding files in legacy text encodings, for `--encoding`
+
+/// The encoding to read files in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Guess from a byte order mark or the contents, falling back to Windows-1252 for text which
+    /// isn't valid UTF-8
+    Auto,
+    #[default]
+    Utf8,
+    /// ISO 8859-1, where every byte is the code point with the same value
+    Latin1,
+    /// Latin-1 with punctuation like curly quotes in place of the C1 control characters
+    Windows1252,
+    /// UTF-16 with a byte order mark, or little-endian without one
+    Utf16,
+    Utf16Le,
+    Utf16Be,
+}
+
+/// Parse an `--encoding` name, ignoring case and punctuation, so that `UTF-8`, `utf8`, and `Utf_8`
+/// are all the same.
+pub fn parse(s: &str) -> Result<Encoding, String> {
+    let name: String = s
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    Ok(match name.as_str() {
+        "auto" => Encoding::Auto,
+        "utf8" => Encoding::Utf8,
+        "latin1" | "iso88591" => Encoding::Latin1,
+        "windows1252" | "cp1252" => Encoding::Windows1252,
+        "utf16" => Encoding::Utf16,
+        "utf16le" => Encoding::Utf16Le,
+        "utf16be" => Encoding::Utf16Be,
+        _ => {
+            return Err(format!(
+                "unknown encoding '{s}', expected auto, utf-8, latin1, windows-1252, utf-16, \
+                 utf-16le, or utf-16be"
+            ))
+        }
+    })
+}
+
+/// Decode a file's contents. Returns the bytes back if they aren't valid in the encoding, so that
+/// they can be shown as binary, unless `lossy` is set, in which case invalid sequences are
+/// replaced with U+FFFD.
+pub fn decode(buf: Vec<u8>, encoding: Encoding, lossy: bool) -> Result<String, Vec<u8>> {
+    match encoding {
+        Encoding::Auto => decode_auto(buf, lossy),
+        Encoding::Utf8 => match String::from_utf8(buf) {
+            Ok(text) => Ok(text),
+            Err(err) if lossy => Ok(String::from_utf8_lossy(err.as_bytes()).into_owned()),
+            Err(err) => Err(err.into_bytes()),
+        },
+        Encoding::Latin1 => Ok(buf.iter().map(|&b| char::from(b)).collect()),
+        Encoding::Windows1252 => Ok(buf.iter().map(|&b| windows1252(b)).collect()),
+        Encoding::Utf16 => match buf.get(..2) {
+            Some([0xfe, 0xff]) => decode_utf16(buf, false, lossy),
+            _ => decode_utf16(buf, true, lossy),
+        },
+        Encoding::Utf16Le => decode_utf16(buf, true, lossy),
+        Encoding::Utf16Be => decode_utf16(buf, false, lossy),
+    }
+}
+
+fn decode_auto(buf: Vec<u8>, lossy: bool) -> Result<String, Vec<u8>> {
+    if let Some(rest) = buf.strip_prefix(b"\xef\xbb\xbf") {
+        return decode(rest.to_vec(), Encoding::Utf8, lossy);
+    }
+    if let Some(little_endian) = guess_utf16(&buf) {
+        return decode_utf16(buf, little_endian, lossy);
+    }
+    match String::from_utf8(buf) {
+        Ok(text) => Ok(text),
+        // control characters other than whitespace mean it's not text in any encoding
+        Err(err)
+            if err
+                .as_bytes()
+                .iter()
+                .any(|&b| b < 0x20 && !b"\t\n\r\x0c\x1b".contains(&b)) =>
+        {
+            Err(err.into_bytes())
+        }
+        Err(err) => decode(err.into_bytes(), Encoding::Windows1252, lossy),
+    }
+}
+
+/// Guess whether `buf` is UTF-16 from a byte order mark or the contents, and if so whether it's
+/// little-endian.
+fn guess_utf16(buf: &[u8]) -> Option<bool> {
+    match buf.get(..2) {
+        Some([0xff, 0xfe]) => return Some(true),
+        Some([0xfe, 0xff]) => return Some(false),
+        _ => (),
+    }
+    // text in UTF-16 without a BOM is mostly ASCII with a NUL in every other byte
+    let nuls = |start: usize| {
+        buf.iter()
+            .skip(start)
+            .step_by(2)
+            .filter(|&&b| b == 0)
+            .count()
+    };
+    if buf.len() >= 4 && buf.len().is_multiple_of(2) {
+        let (even, odd) = (nuls(0), nuls(1));
+        if odd > buf.len() / 4 && even == 0 {
+            return Some(true);
+        }
+        if even > buf.len() / 4 && odd == 0 {
+            return Some(false);
+        }
+    }
+    None
+}
+
+/// Drop a character left incomplete at the end of `buf` by truncating it, so that it isn't decoded
+/// as invalid. Single-byte encodings can't be cut in the middle of a character.
+pub fn trim_partial_char(buf: &mut Vec<u8>, encoding: Encoding) {
+    match encoding {
+        Encoding::Auto if buf.starts_with(b"\xef\xbb\xbf") => trim_partial_utf8(buf),
+        Encoding::Auto => match guess_utf16(&buf[..buf.len() / 2 * 2]) {
+            Some(little_endian) => trim_partial_utf16(buf, little_endian),
+            // if anything before a partial sequence isn't UTF-8, it's all decoded as Windows-1252
+            None => trim_partial_utf8(buf),
+        },
+        Encoding::Utf8 => trim_partial_utf8(buf),
+        Encoding::Latin1 | Encoding::Windows1252 => (),
+        Encoding::Utf16 => {
+            let little_endian = buf.get(..2) != Some(&[0xfe, 0xff]);
+            trim_partial_utf16(buf, little_endian)
+        }
+        Encoding::Utf16Le => trim_partial_utf16(buf, true),
+        Encoding::Utf16Be => trim_partial_utf16(buf, false),
+    }
+}
+
+fn trim_partial_utf8(buf: &mut Vec<u8>) {
+    if let Err(err) = std::str::from_utf8(buf) {
+        if err.error_len().is_none() {
+            buf.truncate(err.valid_up_to());
+        }
+    }
+}
+
+/// Drop an odd byte at the end, and then the first half of a surrogate pair.
+fn trim_partial_utf16(buf: &mut Vec<u8>, little_endian: bool) {
+    buf.truncate(buf.len() / 2 * 2);
+    if let [.., a, b] = buf[..] {
+        let unit = if little_endian {
+            u16::from_le_bytes([a, b])
+        } else {
+            u16::from_be_bytes([a, b])
+        };
+        if (0xd800..0xdc00).contains(&unit) {
+            buf.truncate(buf.len() - 2);
+        }
+    }
+}
+
+fn decode_utf16(buf: Vec<u8>, little_endian: bool, lossy: bool) -> Result<String, Vec<u8>> {
+    let units = buf.chunks_exact(2).map(|pair| {
+        let pair = [pair[0], pair[1]];
+        if little_endian {
+            u16::from_le_bytes(pair)
+        } else {
+            u16::from_be_bytes(pair)
+        }
+    });
+    let mut text = String::with_capacity(buf.len() / 2);
+    for c in char::decode_utf16(units) {
+        match c {
+            Ok(c) => text.push(c),
+            Err(_) if lossy => text.push(char::REPLACEMENT_CHARACTER),
+            Err(_) => return Err(buf),
+        }
+    }
+    // like UTF-8, a BOM at the start is a marker rather than part of the text
+    Ok(text
+        .strip_prefix('\u{feff}')
+        .map(str::to_owned)
+        .unwrap_or(text))
+}
+
+/// Decode a byte in Windows-1252. The five bytes it leaves undefined decode to C1 controls, as in
+/// the WHATWG encoding standard.
+fn windows1252(b: u8) -> char {
+    const HIGH: [char; 32] = [
+        '\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}', '\u{2020}',
+        '\u{2021}', '\u{2c6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8d}', '\u{17d}',
+        '\u{8f}', '\u{90}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}',
+        '\u{2014}', '\u{2dc}', '\u{2122}', '\u{161}', '\u{203a}', '\u{153}', '\u{9d}', '\u{17e}',
+        '\u{178}',
+    ];
+    match b {
+        0x80..=0x9f => HIGH[usize::from(b - 0x80)],
+        b => char::from(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le(s: &str) -> Vec<u8> {
+        s.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    fn utf16be(s: &str) -> Vec<u8> {
+        s.encode_utf16().flat_map(u16::to_be_bytes).collect()
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(parse("UTF-8"), Ok(Encoding::Utf8));
+        assert_eq!(parse("utf_16le"), Ok(Encoding::Utf16Le));
+        assert_eq!(parse("ISO-8859-1"), Ok(Encoding::Latin1));
+        assert_eq!(parse("CP1252"), Ok(Encoding::Windows1252));
+        assert!(parse("ebcdic").is_err());
+    }
+
+    #[test]
+    fn utf8() {
+        assert_eq!(
+            decode("héllo".into(), Encoding::Utf8, false),
+            Ok("héllo".to_owned())
+        );
+        assert_eq!(
+            decode(b"a\xffb".to_vec(), Encoding::Utf8, false),
+            Err(b"a\xffb".to_vec())
+        );
+        assert_eq!(
+            decode(b"a\xffb".to_vec(), Encoding::Utf8, true),
+            Ok("a\u{fffd}b".to_owned())
+        );
+    }
+
+    #[test]
+    fn single_byte() {
+        assert_eq!(
+            decode(b"caf\xe9".to_vec(), Encoding::Latin1, false),
+            Ok("café".to_owned())
+        );
+        assert_eq!(
+            decode(b"\x80\x85".to_vec(), Encoding::Latin1, false),
+            Ok("\u{80}\u{85}".to_owned())
+        );
+        assert_eq!(
+            decode(
+                b"\x93hi\x94 \x80\x81".to_vec(),
+                Encoding::Windows1252,
+                false
+            ),
+            Ok("\u{201c}hi\u{201d} \u{20ac}\u{81}".to_owned())
+        );
+        assert_eq!(windows1252(0x9f), '\u{178}');
+        assert_eq!(windows1252(0xff), 'ÿ');
+    }
+
+    #[test]
+    fn utf16() {
+        let text = "héllo 🦀";
+        assert_eq!(
+            decode(utf16le(text), Encoding::Utf16Le, false),
+            Ok(text.to_owned())
+        );
+        assert_eq!(
+            decode(utf16be(text), Encoding::Utf16Be, false),
+            Ok(text.to_owned())
+        );
+        let with_bom = [b"\xfe\xff".to_vec(), utf16be(text)].concat();
+        assert_eq!(
+            decode(with_bom, Encoding::Utf16, false),
+            Ok(text.to_owned())
+        );
+        assert_eq!(
+            decode(utf16le(text), Encoding::Utf16, false),
+            Ok(text.to_owned())
+        );
+
+        // an unpaired surrogate
+        let bad = [0x3d, 0xd8, 0x41, 0x00];
+        assert_eq!(
+            decode(bad.to_vec(), Encoding::Utf16Le, false),
+            Err(bad.to_vec())
+        );
+        assert_eq!(
+            decode(bad.to_vec(), Encoding::Utf16Le, true),
+            Ok("\u{fffd}A".to_owned())
+        );
+        // an odd trailing byte is dropped
+        assert_eq!(
+            decode(b"a\0b".to_vec(), Encoding::Utf16Le, false),
+            Ok("a".to_owned())
+        );
+    }
+
+    #[test]
+    fn detection() {
+        let auto = |buf: &[u8]| decode(buf.to_vec(), Encoding::Auto, false);
+        assert_eq!(auto(b"plain"), Ok("plain".to_owned()));
+        assert_eq!(auto(b""), Ok(String::new()));
+        assert_eq!(auto(b"\xef\xbb\xbfbom"), Ok("bom".to_owned()));
+        assert_eq!(
+            auto(&[b"\xff\xfe".to_vec(), utf16le("le")].concat()),
+            Ok("le".to_owned())
+        );
+        assert_eq!(
+            auto(&[b"\xfe\xff".to_vec(), utf16be("be")].concat()),
+            Ok("be".to_owned())
+        );
+        assert_eq!(auto(&utf16le("no bom")), Ok("no bom".to_owned()));
+        assert_eq!(auto(&utf16be("no bom")), Ok("no bom".to_owned()));
+        // legacy text falls back to Windows-1252, but binary data isn't text in any encoding
+        assert_eq!(auto(b"caf\xe9\r\n"), Ok("café\r\n".to_owned()));
+        assert_eq!(
+            auto(b"\x7fELF\x02\x01\x01\x00\xff"),
+            Err(b"\x7fELF\x02\x01\x01\x00\xff".to_vec())
+        );
+    }
+
+    fn trimmed(buf: &[u8], encoding: Encoding) -> Vec<u8> {
+        let mut buf = buf.to_vec();
+        trim_partial_char(&mut buf, encoding);
+        buf
+    }
+
+    #[test]
+    fn trims_partial_chars() {
+        // é is 0xc3 0xa9 in UTF-8, and 0xe9 in the single-byte encodings
+        assert_eq!(trimmed(b"ab\xc3", Encoding::Utf8), b"ab");
+        assert_eq!(trimmed(b"ab\xe2\x82", Encoding::Utf8), b"ab");
+        assert_eq!(trimmed(b"ab\xc3\xa9", Encoding::Utf8), b"ab\xc3\xa9");
+        assert_eq!(trimmed(b"ab\xe9", Encoding::Latin1), b"ab\xe9");
+        assert_eq!(trimmed(b"ab\xe2", Encoding::Windows1252), b"ab\xe2");
+
+        let le = utf16le("a\u{1f600}");
+        assert_eq!(trimmed(&le[..5], Encoding::Utf16Le), &le[..2]);
+        assert_eq!(trimmed(&le[..4], Encoding::Utf16Le), &le[..2]);
+        assert_eq!(trimmed(&le, Encoding::Utf16Le), le);
+        let be = utf16be("ab");
+        assert_eq!(trimmed(&be[..3], Encoding::Utf16Be), &be[..2]);
+        let bom = [&b"\xfe\xff"[..], &be].concat();
+        assert_eq!(trimmed(&bom[..5], Encoding::Utf16), &bom[..4]);
+        assert_eq!(trimmed(&le[..3], Encoding::Utf16), &le[..2]);
+    }
+
+    #[test]
+    fn trims_partial_chars_by_guess() {
+        assert_eq!(
+            trimmed(b"caf\xc3\xa9 \xc3", Encoding::Auto),
+            b"caf\xc3\xa9 "
+        );
+        assert_eq!(
+            trimmed(b"\xef\xbb\xbfab\xc3", Encoding::Auto),
+            b"\xef\xbb\xbfab"
+        );
+        // not UTF-8, so the last byte is a whole character in Windows-1252
+        assert_eq!(trimmed(b"caf\xe9 \xc3", Encoding::Auto), b"caf\xe9 \xc3");
+        let le = utf16le("abcd");
+        assert_eq!(trimmed(&le[..7], Encoding::Auto), &le[..6]);
+        let bom = [&b"\xff\xfe"[..], &le].concat();
+        assert_eq!(trimmed(&bom[..5], Encoding::Auto), &bom[..4]);
+    }
+}
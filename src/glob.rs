@@ -0,0 +1,326 @@
+//! Minimal shell-style glob matching and expansion
+//!
+//! Supports `*`, `?`, bracket expressions like `[a-z]` and `[!0-9]`, backslash escapes, and `**` as
+//! a whole path component to match any number of directories. Like the shell, wildcards never match
+//! a leading `.` in a filename.
+
+use std::fs;
+use std::path::Path;
+
+/// Whether `s` contains any glob metacharacters.
+pub fn is_glob(s: &str) -> bool {
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '*' | '?' | '[' => return true,
+            _ => (),
+        }
+    }
+    false
+}
+
+/// Match a single filename (no `/` handling) against a glob pattern.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    fnmatch(pattern, name)
+}
+
+/// Like [`matches`], but wildcards may also match a leading `.`, as in gitignore patterns.
+pub fn fnmatch(pattern: &str, name: &str) -> bool {
+    let pat: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    match_from(&pat, &name)
+}
+
+/// Match a `/`-separated relative path against a pattern, component by component. A `**` component
+/// matches any number of path components, including none. If `literal_dot` is set, a leading `.`
+/// in each component must be matched explicitly, as with [`matches`].
+pub fn matches_path(pattern: &str, path: &str, literal_dot: bool) -> bool {
+    fn components(s: &str) -> Vec<&str> {
+        s.split('/').filter(|c| !c.is_empty()).collect()
+    }
+
+    fn match_components(pat: &[&str], path: &[&str], f: fn(&str, &str) -> bool) -> bool {
+        match pat.split_first() {
+            None => path.is_empty(),
+            Some((&"**", rest)) => (0..=path.len()).any(|i| match_components(rest, &path[i..], f)),
+            Some((p, rest)) => {
+                !path.is_empty() && f(p, path[0]) && match_components(rest, &path[1..], f)
+            }
+        }
+    }
+
+    let f = if literal_dot { matches } else { fnmatch };
+    match_components(&components(pattern), &components(path), f)
+}
+
+fn match_from(pat: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // position to resume from after the most recent '*': (pattern index, name index)
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        let step = match pat.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(1),
+            Some('[') => match_class(&pat[p..], name[n]),
+            Some('\\') if p + 1 < pat.len() => (pat[p + 1] == name[n]).then_some(2),
+            Some(&c) => (c == name[n]).then_some(1),
+            None => None,
+        };
+
+        match step {
+            Some(len) => {
+                p += len;
+                n += 1;
+            }
+            None => match backtrack {
+                // let the last '*' eat one more character and try again
+                Some((bp, bn)) => {
+                    backtrack = Some((bp, bn + 1));
+                    p = bp + 1;
+                    n = bn + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pat[p..].iter().all(|c| *c == '*')
+}
+
+/// Match `c` against the bracket expression at the start of `pat`, returning the length of the
+/// expression if it matched. An unterminated `[` is treated as a literal character.
+fn match_class(pat: &[char], c: char) -> Option<usize> {
+    let mut i = 1;
+    let negate = matches!(pat.get(i), Some('!' | '^'));
+    if negate {
+        i += 1;
+    }
+
+    let mut found = false;
+    let mut first = true;
+    loop {
+        let lo = match pat.get(i) {
+            None => return (c == '[').then_some(1),
+            Some(']') if !first => break,
+            Some('\\') if i + 1 < pat.len() => {
+                i += 1;
+                pat[i]
+            }
+            Some(&lo) => lo,
+        };
+        first = false;
+        i += 1;
+
+        if pat.get(i) == Some(&'-') && pat.get(i + 1).is_some_and(|hi| *hi != ']') {
+            let hi = pat[i + 1];
+            found |= lo <= c && c <= hi;
+            i += 2;
+        } else {
+            found |= lo == c;
+        }
+    }
+
+    (found != negate).then_some(i + 1)
+}
+
+/// Remove backslash escapes from a pattern component that contains no wildcards.
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn join(base: &str, name: &str) -> String {
+    match base {
+        "" => name.to_owned(),
+        b if b.ends_with('/') => format!("{b}{name}"),
+        b => format!("{b}/{name}"),
+    }
+}
+
+fn is_dir(path: &str) -> bool {
+    Path::new(if path.is_empty() { "." } else { path }).is_dir()
+}
+
+/// List the entries of directory `base` (or `.` if empty) whose names match `pattern`.
+fn read_matching(base: &str, pattern: &str, out: &mut Vec<String>) {
+    let Ok(rd) = fs::read_dir(if base.is_empty() { "." } else { base }) else {
+        return;
+    };
+    let mut names: Vec<String> = rd
+        .filter_map(|res| res.ok())
+        .filter_map(|ent| ent.file_name().into_string().ok())
+        .filter(|name| matches(pattern, name))
+        .collect();
+    names.sort();
+    out.extend(names.iter().map(|name| join(base, name)));
+}
+
+/// Add `base` and every directory beneath it to `out`, for `**` components.
+fn push_subdirs(base: &str, out: &mut Vec<String>) {
+    out.push(base.to_owned());
+    let Ok(rd) = fs::read_dir(if base.is_empty() { "." } else { base }) else {
+        return;
+    };
+    let mut dirs: Vec<String> = rd
+        .filter_map(|res| res.ok())
+        .filter(|ent| ent.file_type().is_ok_and(|ft| ft.is_dir()))
+        .filter_map(|ent| ent.file_name().into_string().ok())
+        .filter(|name| !name.starts_with('.'))
+        .collect();
+    dirs.sort();
+    for dir in dirs {
+        push_subdirs(&join(base, &dir), out);
+    }
+}
+
+/// Expand a glob pattern into the sorted list of existing paths it matches.
+pub fn expand(pattern: &str) -> Vec<String> {
+    let mut paths = vec![if pattern.starts_with('/') { "/" } else { "" }.to_owned()];
+    let components: Vec<&str> = pattern.split('/').filter(|c| !c.is_empty()).collect();
+
+    for (i, component) in components.iter().enumerate() {
+        let last = i + 1 == components.len();
+        let mut next = Vec::new();
+        for base in paths.iter().filter(|base| is_dir(base)) {
+            if *component == "**" {
+                let mut dirs = Vec::new();
+                push_subdirs(base, &mut dirs);
+                if last {
+                    // a trailing `**` matches every file beneath the base directory
+                    for dir in dirs {
+                        read_matching(&dir, "*", &mut next);
+                    }
+                } else {
+                    next.extend(dirs);
+                }
+            } else if is_glob(component) {
+                read_matching(base, component, &mut next);
+            } else {
+                let path = join(base, &unescape(component));
+                if !last || fs::symlink_metadata(&path).is_ok() {
+                    next.push(path);
+                }
+            }
+        }
+        paths = next;
+    }
+
+    if components.last() == Some(&"**") {
+        paths.retain(|path| !is_dir(path));
+    }
+    paths.retain(|path| !path.is_empty());
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_globs() {
+        assert!(is_glob("*.txt"));
+        assert!(is_glob("cpu?"));
+        assert!(is_glob("[ab]"));
+        assert!(!is_glob("plain/path"));
+        assert!(!is_glob(r"escaped\*"));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*", "anything"));
+        assert!(matches("*.txt", "notes.txt"));
+        assert!(!matches("*.txt", "notes.txt.bak"));
+        assert!(matches("cpu?", "cpu0"));
+        assert!(!matches("cpu?", "cpu10"));
+        assert!(matches("a*b*c", "aXXbYYc"));
+        assert!(!matches("a*b*c", "aXXbYY"));
+        assert!(matches("**", "x"));
+        assert!(matches("", ""));
+        assert!(!matches("", "x"));
+    }
+
+    #[test]
+    fn brackets() {
+        assert!(matches("[a-c]x", "bx"));
+        assert!(!matches("[a-c]x", "dx"));
+        assert!(matches("[!0-9]", "a"));
+        assert!(matches("[^0-9]", "a"));
+        assert!(!matches("[!0-9]", "5"));
+        assert!(matches("[]]", "]"));
+        assert!(matches("[a-]", "-"));
+        assert!(matches(r"[\]]", "]"));
+        // an unterminated bracket is a literal
+        assert!(matches("a[b", "a[b"));
+        assert!(!matches("a[b", "ab"));
+    }
+
+    #[test]
+    fn escapes() {
+        assert!(matches(r"\*", "*"));
+        assert!(!matches(r"\*", "x"));
+        assert!(matches(r"a\?", "a?"));
+        assert_eq!(unescape(r"a\*b\\"), r"a*b\");
+    }
+
+    #[test]
+    fn hidden_files() {
+        assert!(!matches("*", ".hidden"));
+        assert!(!matches("?hidden", ".hidden"));
+        assert!(matches(".*", ".hidden"));
+        assert!(fnmatch("*", ".hidden"));
+    }
+
+    #[test]
+    fn paths() {
+        assert!(matches_path("a/*/c", "a/b/c", true));
+        assert!(!matches_path("a/*/c", "a/b/d/c", true));
+        assert!(matches_path("a/**/c", "a/c", true));
+        assert!(matches_path("a/**/c", "a/b/d/c", true));
+        assert!(matches_path("**/*.rs", "src/main.rs", true));
+        assert!(matches_path("a//b/", "a/b", true));
+        assert!(!matches_path("*/x", ".git/x", true));
+        assert!(matches_path("*/x", ".git/x", false));
+    }
+
+    #[test]
+    fn expands() {
+        let dir = std::env::temp_dir().join(format!("tabby-glob-{}", std::process::id()));
+        for path in ["a/x.txt", "a/y.log", "a/b/z.txt", "a/.hidden.txt"] {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        let base = dir.to_str().unwrap();
+        let expand = |pattern: &str| -> Vec<String> {
+            expand(&format!("{base}/{pattern}"))
+                .into_iter()
+                .map(|path| path[base.len() + 1..].to_owned())
+                .collect()
+        };
+        assert_eq!(expand("a/*.txt"), ["a/x.txt"]);
+        assert_eq!(expand("a/**/*.txt"), ["a/x.txt", "a/b/z.txt"]);
+        assert_eq!(expand("a/**"), ["a/x.txt", "a/y.log", "a/b/z.txt"]);
+        assert_eq!(expand("*/b/z.txt"), ["a/b/z.txt"]);
+        assert!(expand("a/*.md").is_empty());
+        assert!(expand("a/missing").is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,246 @@
+//! Simple syntax highlighting for multiline files shown on a terminal
+//!
+//! This isn't a real parser for any language, just a tokenizer which knows where comments, strings,
+//! numbers, and keywords start and end in a handful of languages common among small config files
+//! and scripts. The language is guessed from the filename extension or a `#!` line.
+
+use std::fmt::Write;
+
+use crate::color::Style;
+
+struct Lang {
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    /// Whether backslashes are taken literally in single-quoted strings
+    raw_single_quotes: bool,
+    keywords: &'static [&'static str],
+}
+
+const SHELL: Lang = Lang {
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    raw_single_quotes: true,
+    keywords: &[
+        "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+        "in", "local", "readonly", "return", "then", "until", "while",
+    ],
+};
+
+const PYTHON: Lang = Lang {
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    raw_single_quotes: false,
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+        "elif", "else", "except", "False", "finally", "for", "from", "global", "if", "import",
+        "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return", "True",
+        "try", "while", "with", "yield",
+    ],
+};
+
+const RUST: Lang = Lang {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"'],
+    raw_single_quotes: false,
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+        "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait",
+        "true", "type", "unsafe", "use", "where", "while",
+    ],
+};
+
+const C: Lang = Lang {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\''],
+    raw_single_quotes: false,
+    keywords: &[
+        "break", "case", "char", "const", "continue", "default", "do", "double", "else", "enum",
+        "extern", "float", "for", "goto", "if", "int", "long", "return", "short", "signed",
+        "sizeof", "static", "struct", "switch", "typedef", "union", "unsigned", "void", "volatile",
+        "while", "#define", "#include", "#if", "#ifdef", "#ifndef", "#endif",
+    ],
+};
+
+const JAVASCRIPT: Lang = Lang {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\'', '`'],
+    raw_single_quotes: false,
+    keywords: &[
+        "async",
+        "await",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "else",
+        "export",
+        "false",
+        "for",
+        "function",
+        "if",
+        "import",
+        "in",
+        "let",
+        "new",
+        "null",
+        "of",
+        "return",
+        "switch",
+        "this",
+        "throw",
+        "true",
+        "try",
+        "typeof",
+        "undefined",
+        "var",
+        "while",
+    ],
+};
+
+/// JSON, YAML, TOML, INI, and similar config formats
+const CONFIG: Lang = Lang {
+    line_comments: &["#", ";"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    raw_single_quotes: true,
+    keywords: &["true", "false", "null", "yes", "no", "on", "off"],
+};
+
+const JSON: Lang = Lang {
+    line_comments: &[],
+    block_comment: None,
+    quotes: &['"'],
+    raw_single_quotes: false,
+    keywords: &["true", "false", "null"],
+};
+
+const COMMENT: &str = "2";
+const STRING: &str = "32";
+const NUMBER: &str = "35";
+const KEYWORD: &str = "34";
+
+fn detect(name: &str, text: &str) -> Option<&'static Lang> {
+    if let Some(shebang) = text.lines().next().and_then(|line| line.strip_prefix("#!")) {
+        let mut words = shebang.split_whitespace();
+        let mut prog = words.next()?.rsplit('/').next()?;
+        if prog == "env" {
+            prog = words.find(|w| !w.starts_with('-'))?;
+        }
+        let prog = prog.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+        return match prog {
+            "sh" | "bash" | "dash" | "ksh" | "zsh" | "ash" => Some(&SHELL),
+            "python" => Some(&PYTHON),
+            "node" => Some(&JAVASCRIPT),
+            _ => None,
+        };
+    }
+
+    let base = name.rsplit('/').next().unwrap_or(name);
+    let ext = base.rsplit_once('.').map_or("", |(_, ext)| ext);
+    match ext {
+        "sh" | "bash" | "zsh" => Some(&SHELL),
+        "py" => Some(&PYTHON),
+        "rs" => Some(&RUST),
+        "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" => Some(&C),
+        "js" | "mjs" | "ts" => Some(&JAVASCRIPT),
+        "json" => Some(&JSON),
+        "yaml" | "yml" | "toml" | "ini" | "conf" | "cfg" => Some(&CONFIG),
+        _ => None,
+    }
+}
+
+/// Highlight a file's contents, if its language can be guessed from its name or contents.
+pub fn highlight(name: &str, text: &str) -> Option<String> {
+    let lang = detect(name, text)?;
+    let mut out = String::with_capacity(text.len() * 2);
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        let (len, style) =
+            if let Some(marker) = lang.line_comments.iter().find(|m| rest.starts_with(**m)) {
+                let len = rest.find('\n').unwrap_or(rest.len());
+                // things like "$#" and "a#b" in shell aren't comments
+                let at_word_start = out.is_empty() || out.ends_with(|c: char| c.is_whitespace());
+                if marker.len() == 1 && !at_word_start {
+                    (c.len_utf8(), None)
+                } else {
+                    (len, Some(COMMENT))
+                }
+            } else if let Some((start, end)) = lang
+                .block_comment
+                .filter(|(start, _)| rest.starts_with(start))
+            {
+                let len = rest[start.len()..]
+                    .find(end)
+                    .map_or(rest.len(), |i| start.len() + i + end.len());
+                (len, Some(COMMENT))
+            } else if let Some(len) = lang
+                .quotes
+                .contains(&c)
+                .then(|| string_len(lang, rest, c))
+                .flatten()
+            {
+                (len, Some(STRING))
+            } else if c.is_ascii_digit() {
+                let len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '_'));
+                (len.unwrap_or(rest.len()), Some(NUMBER))
+            } else if c.is_alphabetic() || c == '_' || c == '#' {
+                let len = rest[c.len_utf8()..]
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .map_or(rest.len(), |i| i + c.len_utf8());
+                let is_keyword = lang.keywords.contains(&&rest[..len]);
+                (len, is_keyword.then_some(KEYWORD))
+            } else {
+                (c.len_utf8(), None)
+            };
+
+        let (token, tail) = rest.split_at(len);
+        match style {
+            Some(style) => paint_lines(&mut out, &Style::new(style), token),
+            None => out.push_str(token),
+        }
+        rest = tail;
+    }
+    Some(out)
+}
+
+/// Get the length of a string literal starting with `quote`, including both quotes. Quotes which
+/// aren't closed on the same line, like apostrophes in prose, don't start a string.
+fn string_len(lang: &Lang, text: &str, quote: char) -> Option<usize> {
+    let escapes = !(quote == '\'' && lang.raw_single_quotes);
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        if c == '\n' {
+            return None;
+        } else if escaped {
+            escaped = false;
+        } else if c == '\\' && escapes {
+            escaped = true;
+        } else if c == quote {
+            return Some(i + c.len_utf8());
+        }
+    }
+    None
+}
+
+/// Paint each line of a token separately, so that colors never span a line break.
+fn paint_lines(out: &mut String, style: &Style, token: &str) {
+    for (i, line) in token.split('\n').enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        if !line.is_empty() {
+            write!(out, "{}", style.paint(line)).unwrap();
+        }
+    }
+}
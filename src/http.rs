@@ -0,0 +1,82 @@
+//! Fetching `http://` and `https://` URLs
+//!
+//! URLs are always recognized so that they aren't mistaken for paths, but fetching them requires
+//! the `http` feature. Requests are made by running `curl`, which takes care of TLS, redirects,
+//! and proxy settings.
+
+use std::io;
+#[cfg(feature = "http")]
+use std::io::Read;
+#[cfg(feature = "http")]
+use std::process::{Command, Stdio};
+
+use crate::text::{ReadOptions, Text};
+
+/// How long to wait for a single URL before giving up
+#[cfg(feature = "http")]
+const TIMEOUT_SECS: &str = "30";
+
+/// Whether a FILE argument should be fetched as a URL.
+pub fn is_url(s: &str) -> bool {
+    s.starts_with("http://") || s.starts_with("https://")
+}
+
+/// Fetch a URL and process its body like a file's contents.
+#[cfg(feature = "http")]
+pub fn fetch(url: &str, opts: &ReadOptions) -> Text {
+    let child = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args(["--max-time", TIMEOUT_SECS])
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Text::Err(io::Error::new(err.kind(), "curl is required to fetch URLs"))
+        }
+        Err(err) => return Text::Err(err),
+    };
+
+    let mut body = Vec::new();
+    let stdout = child.stdout.take().unwrap();
+    let res = match opts.read_limit() {
+        Some(limit) => stdout.take(limit).read_to_end(&mut body),
+        None => { stdout }.read_to_end(&mut body),
+    };
+    // stdout is closed by now, so curl will exit even if we stopped reading early
+    let mut stderr = String::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        let _ = pipe.read_to_string(&mut stderr);
+    }
+    let status = child.wait();
+
+    if let Err(err) = res {
+        return Text::Err(err);
+    }
+    let hit_limit = opts
+        .read_limit()
+        .is_some_and(|limit| body.len() as u64 >= limit);
+    match status {
+        Ok(status) if !status.success() && !hit_limit => {
+            let msg = stderr.trim().trim_start_matches("curl: ");
+            Text::Err(io::Error::other(if msg.is_empty() {
+                "curl failed"
+            } else {
+                msg
+            }))
+        }
+        Err(err) => Text::Err(err),
+        _ => Text::from_bytes(body, opts),
+    }
+}
+
+#[cfg(not(feature = "http"))]
+pub fn fetch(_url: &str, _opts: &ReadOptions) -> Text {
+    Text::Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "tabby was built without URL support (the `http` feature)",
+    ))
+}
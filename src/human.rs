@@ -0,0 +1,115 @@
+//! Readable renderings of raw numbers from sysfs, for `--human`
+//!
+//! The kernel reports most measurements as integers in fixed units, like microwatt-hours for a
+//! battery's energy or millidegrees for a temperature. The unit can only be told from the file's
+//! name, following the conventions in the sysfs ABI documentation.
+
+/// Describe a one-line value in its units, like `48.2 Wh` for `energy_now: 48230000`. Returns None
+/// for values which aren't a plain integer, and for files whose units aren't known.
+pub fn describe(label: &str, value: &str) -> Option<String> {
+    let n: i64 = value.trim().parse().ok()?;
+    let name = label.rsplit('/').next().unwrap_or(label);
+    let parent = label.rsplit('/').nth(1).unwrap_or("");
+
+    // the power_supply class, in micro-units, like energy_now and voltage_min_design
+    const SUPPLY: &[(&str, &str)] = &[
+        ("energy", "Wh"),
+        ("power", "W"),
+        ("charge", "Ah"),
+        ("current", "A"),
+        ("voltage", "V"),
+    ];
+    const READINGS: &[&str] = &[
+        "now",
+        "avg",
+        "full",
+        "full_design",
+        "empty",
+        "empty_design",
+        "min",
+        "max",
+        "min_design",
+        "max_design",
+        "counter",
+        "ocv",
+        "boot",
+    ];
+    if let Some((kind, reading)) = name.split_once('_') {
+        if READINGS.contains(&reading) {
+            if let Some((_, unit)) = SUPPLY.iter().find(|(k, _)| *k == kind) {
+                return Some(scaled(n as f64 / 1e6, unit));
+            }
+        }
+    }
+
+    // hwmon sensors like temp1_input and in0_max, in milli- or micro-units
+    if let Some((kind, _)) = name
+        .split_once('_')
+        .filter(|(_, reading)| ["input", "min", "max", "crit", "average"].contains(reading))
+    {
+        match kind.trim_end_matches(|c: char| c.is_ascii_digit()) {
+            "temp" => return Some(scaled(n as f64 / 1e3, "°C")),
+            "in" => return Some(scaled(n as f64 / 1e3, "V")),
+            "curr" => return Some(scaled(n as f64 / 1e3, "A")),
+            "power" => return Some(scaled(n as f64 / 1e6, "W")),
+            "energy" => return Some(scaled(n as f64 / 1e6, "J")),
+            "fan" => return Some(format!("{n} RPM")),
+            _ => (),
+        }
+    }
+    // thermal zones, in millidegrees
+    if name == "temp" || (name.starts_with("trip_point_") && name.ends_with("_temp")) {
+        return Some(scaled(n as f64 / 1e3, "°C"));
+    }
+
+    // cpufreq and devfreq frequencies, which are in kHz and Hz
+    if name.ends_with("_freq") || name.ends_with("_frequency") {
+        let hz = if parent == "cpufreq" || parent.starts_with("policy") {
+            n.saturating_mul(1000)
+        } else {
+            n
+        };
+        return Some(frequency(hz));
+    }
+
+    const BLOCK: &[&str] = &[
+        "sd", "hd", "vd", "xvd", "nvme", "mmcblk", "dm-", "loop", "md", "zram",
+    ];
+    let bytes = u64::try_from(n).ok()?;
+    if name.ends_with("_kb") {
+        Some(crate::size::format(bytes.saturating_mul(1024)))
+    } else if name == "size" && BLOCK.iter().any(|prefix| parent.starts_with(prefix)) {
+        // block devices count 512-byte sectors
+        Some(crate::size::format(bytes.saturating_mul(512)))
+    } else if name.contains("bytes") {
+        Some(crate::size::format(bytes))
+    } else {
+        None
+    }
+}
+
+/// Format a value to three significant digits with a unit, like `48.2 Wh` or `0.512 A`.
+fn scaled(value: f64, unit: &str) -> String {
+    let digits = match value.abs() {
+        v if v >= 100.0 || v == 0.0 => 0,
+        v if v >= 10.0 => 1,
+        v if v >= 1.0 => 2,
+        _ => 3,
+    };
+    let s = format!("{value:.digits$}");
+    let s = if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        &s
+    };
+    format!("{s} {unit}")
+}
+
+fn frequency(hz: i64) -> String {
+    match hz.unsigned_abs() {
+        h if h >= 1_000_000_000 => scaled(hz as f64 / 1e9, "GHz"),
+        h if h >= 1_000_000 => scaled(hz as f64 / 1e6, "MHz"),
+        h if h >= 1_000 => scaled(hz as f64 / 1e3, "kHz"),
+        _ => format!("{hz} Hz"),
+    }
+}
@@ -0,0 +1,261 @@
+//! Support for skipping files matched by `.gitignore` rules
+//!
+//! This covers the common subset of gitignore semantics: comments, `!` negation, trailing-`/`
+//! directory-only patterns, anchoring for patterns containing a `/`, and `**`. Rules come from the
+//! global excludes file, the repository's `.git/info/exclude`, and `.gitignore` files in each
+//! directory, with later (deeper) rules taking precedence.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::glob;
+
+#[derive(Debug)]
+struct Rule {
+    /// Directory containing the ignore file, relative to the repository root
+    base: String,
+    pattern: String,
+    negate: bool,
+    dir_only: bool,
+    /// Match against the whole path below `base` rather than only the filename
+    anchored: bool,
+}
+
+impl Rule {
+    fn parse(line: &str, base: &str) -> Option<Rule> {
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        // trailing spaces are ignored unless escaped
+        let trimmed = line.trim_end_matches(' ');
+        let line = if trimmed.ends_with('\\') && trimmed.len() < line.len() {
+            &line[..trimmed.len() + 1]
+        } else {
+            trimmed
+        };
+
+        let (negate, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        if line.is_empty() {
+            return None;
+        }
+
+        Some(Rule {
+            base: base.to_owned(),
+            anchored: line.contains('/'),
+            pattern: line.trim_start_matches('/').to_owned(),
+            negate,
+            dir_only,
+        })
+    }
+
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let sub = if self.base.is_empty() {
+            path
+        } else {
+            match path
+                .strip_prefix(&self.base)
+                .and_then(|p| p.strip_prefix('/'))
+            {
+                Some(sub) => sub,
+                None => return false,
+            }
+        };
+
+        if self.anchored {
+            glob::matches_path(&self.pattern, sub, false)
+        } else {
+            let name = sub.rsplit('/').next().unwrap_or(sub);
+            glob::fnmatch(&self.pattern, name)
+        }
+    }
+}
+
+/// A stack of gitignore rules for the directories currently being walked
+#[derive(Debug)]
+pub struct Gitignore {
+    rules: Vec<Rule>,
+    /// Path of the walk root relative to the repository root
+    prefix: String,
+}
+
+impl Gitignore {
+    /// Load the rules which apply to files under `root`. Returns `None` if `root` isn't inside a
+    /// git repository.
+    pub fn new(root: &Path) -> Option<Gitignore> {
+        let root = fs::canonicalize(root).ok()?;
+        let repo = root.ancestors().find(|dir| dir.join(".git").exists())?;
+        let prefix = root.strip_prefix(repo).ok()?;
+
+        let mut ign = Gitignore {
+            rules: Vec::new(),
+            prefix: prefix.to_str()?.to_owned(),
+        };
+        if let Some(global) = global_excludes() {
+            ign.load(&global, "");
+        }
+        ign.load(&repo.join(".git/info/exclude"), "");
+
+        // .gitignore files in the directories above the walk root. The walk root's own file is
+        // loaded by push() along with every other directory visited.
+        let mut base = PathBuf::new();
+        for component in prefix.components() {
+            let dir = base.to_str()?.to_owned();
+            ign.load(&repo.join(&base).join(".gitignore"), &dir);
+            base.push(component);
+        }
+        Some(ign)
+    }
+
+    fn repo_path(&self, rel: &str) -> String {
+        match (self.prefix.as_str(), rel) {
+            ("", rel) => rel.to_owned(),
+            (prefix, "") => prefix.to_owned(),
+            (prefix, rel) => format!("{prefix}/{rel}"),
+        }
+    }
+
+    fn load(&mut self, file: &Path, base: &str) {
+        if let Ok(text) = fs::read_to_string(file) {
+            self.rules
+                .extend(text.lines().filter_map(|line| Rule::parse(line, base)));
+        }
+    }
+
+    /// Push the rules from the `.gitignore` in directory `dir` (located at `rel` relative to the
+    /// walk root), returning a marker to pass to [`Gitignore::pop`] when leaving that directory.
+    pub fn push(&mut self, dir: &Path, rel: &str) -> usize {
+        let len = self.rules.len();
+        let base = self.repo_path(rel);
+        self.load(&dir.join(".gitignore"), &base);
+        len
+    }
+
+    pub fn pop(&mut self, marker: usize) {
+        self.rules.truncate(marker);
+    }
+
+    /// Whether the path `rel`, relative to the walk root, is ignored.
+    pub fn is_ignored(&self, rel: &str, is_dir: bool) -> bool {
+        let path = self.repo_path(rel);
+        if is_dir && path.rsplit('/').next() == Some(".git") {
+            return true;
+        }
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(&path, is_dir))
+            .is_some_and(|rule| !rule.negate)
+    }
+}
+
+fn global_excludes() -> Option<PathBuf> {
+    match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("git/ignore")),
+        _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(".config/git/ignore")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(text: &str) -> Gitignore {
+        Gitignore {
+            rules: text
+                .lines()
+                .filter_map(|line| Rule::parse(line, ""))
+                .collect(),
+            prefix: String::new(),
+        }
+    }
+
+    #[test]
+    fn parses_lines() {
+        assert!(Rule::parse("", "").is_none());
+        assert!(Rule::parse("# comment", "").is_none());
+        assert!(Rule::parse("!", "").is_none());
+        assert!(Rule::parse("/", "").is_none());
+
+        let rule = Rule::parse("!/build/", "").unwrap();
+        assert!(rule.negate && rule.dir_only && rule.anchored);
+        assert_eq!(rule.pattern, "build");
+
+        assert_eq!(Rule::parse(r"\#notes", "").unwrap().pattern, "#notes");
+        assert!(!Rule::parse(r"\!x", "").unwrap().negate);
+        assert_eq!(Rule::parse("trailing  ", "").unwrap().pattern, "trailing");
+        assert_eq!(Rule::parse(r"space\ ", "").unwrap().pattern, r"space\ ");
+    }
+
+    #[test]
+    fn names_and_paths() {
+        let ign = rules("*.o\n/root.txt\ndocs/*.md\nbuild/\n");
+        assert!(ign.is_ignored("main.o", false));
+        assert!(ign.is_ignored("src/deep/main.o", false));
+        assert!(ign.is_ignored("root.txt", false));
+        assert!(!ign.is_ignored("sub/root.txt", false));
+        assert!(ign.is_ignored("docs/a.md", false));
+        assert!(!ign.is_ignored("docs/sub/a.md", false));
+        assert!(ign.is_ignored("build", true));
+        assert!(ign.is_ignored("src/build", true));
+        assert!(!ign.is_ignored("build", false));
+        assert!(!ign.is_ignored("main.c", false));
+        assert!(ign.is_ignored(".git", true));
+    }
+
+    #[test]
+    fn later_rules_win() {
+        let ign = rules("*.log\n!keep.log\n");
+        assert!(ign.is_ignored("a.log", false));
+        assert!(!ign.is_ignored("keep.log", false));
+        let ign = rules("!keep.log\n*.log\n");
+        assert!(ign.is_ignored("keep.log", false));
+    }
+
+    #[test]
+    fn double_star() {
+        let ign = rules("a/**/z\n**/tmp\n");
+        assert!(ign.is_ignored("a/z", false));
+        assert!(ign.is_ignored("a/b/c/z", false));
+        assert!(ign.is_ignored("tmp", true));
+        assert!(ign.is_ignored("x/y/tmp", false));
+    }
+
+    #[test]
+    fn nested_files() {
+        let dir = env::temp_dir().join(format!("tabby-ignore-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/.gitignore"), "*.tmp\n/only-here\n").unwrap();
+
+        let mut ign = rules("");
+        let marker = ign.push(&dir.join("sub"), "sub");
+        assert!(ign.is_ignored("sub/a.tmp", false));
+        assert!(ign.is_ignored("sub/deeper/a.tmp", false));
+        assert!(ign.is_ignored("sub/only-here", false));
+        assert!(!ign.is_ignored("sub/deeper/only-here", false));
+        assert!(!ign.is_ignored("a.tmp", false));
+        ign.pop(marker);
+        assert!(!ign.is_ignored("sub/a.tmp", false));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn walk_root_below_the_repository() {
+        let mut ign = rules("");
+        ign.prefix = "pkg".to_owned();
+        ign.rules.extend(Rule::parse("/pkg/gen", ""));
+        assert!(ign.is_ignored("gen", false));
+        assert!(!ign.is_ignored("other/gen", false));
+    }
+}
@@ -0,0 +1,447 @@
+//! DEFLATE (RFC 1951) and gzip (RFC 1952) decompression
+//!
+//! This is a straightforward, unoptimized decoder in the style of zlib's `puff`, which is plenty
+//! fast for the small archives and log snapshots tabby deals with.
+
+use std::io;
+
+const MAX_BITS: usize = 15;
+
+/// Base lengths and extra bits for length codes 257..285
+const LEN_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LEN_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// Base offsets and extra bits for distance codes 0..29
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order in which code length code lengths are stored in a dynamic block header
+const CLEN_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_owned())
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bitbuf: u32,
+    bitcnt: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, need: u32) -> io::Result<u32> {
+        while self.bitcnt < need {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .ok_or_else(|| invalid("unexpected end of compressed data"))?;
+            self.pos += 1;
+            self.bitbuf |= (byte as u32) << self.bitcnt;
+            self.bitcnt += 8;
+        }
+        let val = self.bitbuf & ((1u32 << need) - 1);
+        self.bitbuf = self.bitbuf.checked_shr(need).unwrap_or(0);
+        self.bitcnt -= need;
+        Ok(val)
+    }
+
+    /// Discard any bits left in the current byte.
+    fn align(&mut self) {
+        self.bitbuf = 0;
+        self.bitcnt = 0;
+    }
+}
+
+/// A canonical Huffman code, stored as the number of codes of each length and the symbols
+/// ordered by code
+struct Huffman {
+    count: [u16; MAX_BITS + 1],
+    symbol: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut count = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            count[len as usize] += 1;
+        }
+        count[0] = 0;
+
+        let mut offs = [0u16; MAX_BITS + 1];
+        for len in 1..MAX_BITS {
+            offs[len + 1] = offs[len] + count[len];
+        }
+        let mut symbol = vec![0; lengths.len()];
+        for (sym, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbol[offs[len as usize] as usize] = sym as u16;
+                offs[len as usize] += 1;
+            }
+        }
+        Huffman { count, symbol }
+    }
+
+    fn decode(&self, br: &mut BitReader) -> io::Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..=MAX_BITS {
+            code |= br.bits(1)? as i32;
+            let count = self.count[len] as i32;
+            if code - count < first {
+                return Ok(self.symbol[(index + (code - first)) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("invalid Huffman code"))
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(br: &mut BitReader) -> io::Result<(Huffman, Huffman)> {
+    let nlen = br.bits(5)? as usize + 257;
+    let ndist = br.bits(5)? as usize + 1;
+    let ncode = br.bits(4)? as usize + 4;
+    if nlen > 286 || ndist > 30 {
+        return Err(invalid("bad dynamic block header"));
+    }
+
+    let mut clens = [0u8; 19];
+    for &idx in &CLEN_ORDER[..ncode] {
+        clens[idx] = br.bits(3)? as u8;
+    }
+    let clen_code = Huffman::new(&clens);
+
+    let mut lengths = vec![0u8; nlen + ndist];
+    let mut i = 0;
+    while i < lengths.len() {
+        let sym = clen_code.decode(br)?;
+        let (val, repeat) = match sym {
+            0..=15 => (sym as u8, 1),
+            16 => {
+                let prev = *lengths[..i]
+                    .last()
+                    .ok_or_else(|| invalid("repeated length with no previous length"))?;
+                (prev, 3 + br.bits(2)? as usize)
+            }
+            17 => (0, 3 + br.bits(3)? as usize),
+            _ => (0, 11 + br.bits(7)? as usize),
+        };
+        if i + repeat > lengths.len() {
+            return Err(invalid("too many code lengths"));
+        }
+        lengths[i..i + repeat].fill(val);
+        i += repeat;
+    }
+
+    Ok((
+        Huffman::new(&lengths[..nlen]),
+        Huffman::new(&lengths[nlen..]),
+    ))
+}
+
+/// Decode a compressed block, stopping early once `out` holds `limit` bytes.
+fn inflate_block(
+    br: &mut BitReader,
+    out: &mut Vec<u8>,
+    lit: &Huffman,
+    dist: &Huffman,
+    limit: usize,
+) -> io::Result<()> {
+    while out.len() < limit {
+        let sym = lit.decode(br)? as usize;
+        match sym {
+            0..=255 => out.push(sym as u8),
+            256 => return Ok(()),
+            _ => {
+                let idx = sym - 257;
+                if idx >= LEN_BASE.len() {
+                    return Err(invalid("invalid length code"));
+                }
+                let len = LEN_BASE[idx] as usize + br.bits(LEN_EXTRA[idx] as u32)? as usize;
+
+                let didx = dist.decode(br)? as usize;
+                if didx >= DIST_BASE.len() {
+                    return Err(invalid("invalid distance code"));
+                }
+                let dist = DIST_BASE[didx] as usize + br.bits(DIST_EXTRA[didx] as u32)? as usize;
+                if dist > out.len() {
+                    return Err(invalid("distance too far back"));
+                }
+
+                // copies may overlap their own output, so go byte by byte
+                let start = out.len() - dist;
+                for i in 0..len.min(limit - out.len()) {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Decompress a raw DEFLATE stream, returning the output and the number of input bytes used. If
+/// `limit` is given, stop once the output reaches that many bytes, so that a small file which
+/// expands enormously can't use up all the memory.
+pub fn inflate(data: &[u8], limit: Option<u64>) -> io::Result<(Vec<u8>, usize)> {
+    let limit = limit.map_or(usize::MAX, |l| usize::try_from(l).unwrap_or(usize::MAX));
+    let mut br = BitReader {
+        data,
+        pos: 0,
+        bitbuf: 0,
+        bitcnt: 0,
+    };
+    let mut out = Vec::new();
+
+    loop {
+        if out.len() >= limit {
+            return Ok((out, br.pos));
+        }
+        let last = br.bits(1)? == 1;
+        match br.bits(2)? {
+            0 => {
+                br.align();
+                let header = data
+                    .get(br.pos..br.pos + 4)
+                    .ok_or_else(|| invalid("unexpected end of compressed data"))?;
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                let nlen = u16::from_le_bytes([header[2], header[3]]) as usize;
+                if len != !nlen & 0xffff {
+                    return Err(invalid("stored block length mismatch"));
+                }
+                br.pos += 4;
+                let block = data
+                    .get(br.pos..br.pos + len)
+                    .ok_or_else(|| invalid("unexpected end of compressed data"))?;
+                out.extend_from_slice(&block[..len.min(limit - out.len())]);
+                br.pos += len;
+            }
+            1 => {
+                let (lit, dist) = fixed_codes();
+                inflate_block(&mut br, &mut out, &lit, &dist, limit)?;
+            }
+            2 => {
+                let (lit, dist) = dynamic_codes(&mut br)?;
+                inflate_block(&mut br, &mut out, &lit, &dist, limit)?;
+            }
+            _ => return Err(invalid("invalid block type")),
+        }
+        if last {
+            return Ok((out, br.pos));
+        }
+    }
+}
+
+/// Compute the CRC-32 checksum used by gzip and zip.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 {
+                0xedb88320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *entry = c;
+    }
+
+    !data.iter().fold(!0u32, |crc, &b| {
+        table[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Whether `data` starts with the gzip magic number.
+pub fn is_gzip(data: &[u8]) -> bool {
+    data.starts_with(&[0x1f, 0x8b])
+}
+
+/// Decompress gzip data, which may consist of several concatenated members. If `limit` is given,
+/// stop once the output reaches that many bytes, without checking the rest.
+pub fn gunzip(mut data: &[u8], limit: Option<u64>) -> io::Result<Vec<u8>> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    let truncated = || invalid("truncated gzip header");
+    let mut out = Vec::new();
+    while !data.is_empty() {
+        if data.len() < 10 || !is_gzip(data) || data[2] != 8 {
+            return Err(invalid("not in gzip format"));
+        }
+        let flags = data[3];
+        let mut pos = 10;
+
+        if flags & FEXTRA != 0 {
+            let xlen = data.get(pos..pos + 2).ok_or_else(truncated)?;
+            pos += 2 + u16::from_le_bytes([xlen[0], xlen[1]]) as usize;
+        }
+        for flag in [FNAME, FCOMMENT] {
+            if flags & flag != 0 {
+                let nul = data[pos.min(data.len())..]
+                    .iter()
+                    .position(|b| *b == 0)
+                    .ok_or_else(truncated)?;
+                pos += nul + 1;
+            }
+        }
+        if flags & FHCRC != 0 {
+            pos += 2;
+        }
+
+        let left = limit.map(|l| l - out.len() as u64);
+        let (member, used) = inflate(data.get(pos..).ok_or_else(truncated)?, left)?;
+        if left.is_some_and(|left| member.len() as u64 >= left) {
+            // the checksum covers the whole member, so it can't be checked
+            out.extend_from_slice(&member);
+            return Ok(out);
+        }
+        pos += used;
+        let trailer = data
+            .get(pos..pos + 8)
+            .ok_or_else(|| invalid("truncated gzip trailer"))?;
+        let crc = u32::from_le_bytes(trailer[..4].try_into().unwrap());
+        let isize = u32::from_le_bytes(trailer[4..].try_into().unwrap());
+        if crc != crc32(&member) || isize != member.len() as u32 {
+            return Err(invalid("gzip checksum mismatch"));
+        }
+
+        out.extend_from_slice(&member);
+        data = &data[pos + 8..];
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// "hi\n", as written by gzip
+    const HI_GZ: &[u8] = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\xcb\xc8\xe4\x02\x00\x7a\x7a\x6f\xed\x03\x00\x00\x00";
+
+    #[test]
+    fn fixed_block() {
+        let data = b"\xcb\x48\xcd\xc9\xc9\x57\xc8\x40\x90\x5c\x00";
+        let (out, used) = inflate(data, None).unwrap();
+        assert_eq!(out, b"hello hello hello\n");
+        assert_eq!(used, data.len());
+    }
+
+    #[test]
+    fn stored_block() {
+        let data = b"\x01\x03\x00\xfc\xff\x61\x62\x63";
+        assert_eq!(inflate(data, None).unwrap().0, b"abc");
+        let bad_len = b"\x01\x03\x00\xfc\xfe\x61\x62\x63";
+        assert!(inflate(bad_len, None).is_err());
+        assert!(inflate(&data[..6], None).is_err());
+    }
+
+    #[test]
+    fn dynamic_block() {
+        let data = b"\xb5\xcb\xc9\x11\x80\x20\x10\x44\xd1\xbb\x51\x74\x1e\x46\x03\x3a\x02\x0a\x0c\
+            \xb2\xa8\x10\xbd\x53\xe6\xe0\xb1\xeb\xbf\xae\x96\x70\x36\xb7\x1c\xd0\x99\xef\x88\x8d\
+            \x1f\xec\x2d\xa4\x02\xbe\x28\xa3\x4a\xf6\x6a\x74\xac\x6c\xe6\x6f\xfd\x83\x93\x12\x17\
+            \x3a\xb4\xa0\xdb\x55\x8b\xcd\x5d\x24\x69\x50\x84\x77\x67\xe3\x2c\x5f\x53\xa6\x17";
+        let want = [
+            "the quick brown fox jumps over the lazy dog; ".repeat(3),
+            "pack my box with five dozen liquor jugs\n".to_owned(),
+        ]
+        .concat();
+        assert_eq!(inflate(data, None).unwrap().0, want.as_bytes());
+    }
+
+    #[test]
+    fn malformed() {
+        // block type 3 is reserved
+        assert!(inflate(b"\x07", None).is_err());
+        assert!(inflate(b"", None).is_err());
+        // a fixed block whose first code is a match, with nothing to copy from
+        assert!(inflate(b"\x03\x02", None).is_err());
+        // truncated in the middle of a fixed block
+        assert!(inflate(b"\xcb\x48\xcd", None).is_err());
+    }
+
+    #[test]
+    fn limits() {
+        // 1000 'a's, mostly as back-references
+        let data = b"\x4b\x4c\x1c\x05\xa3\x60\x14\x0c\x77\x00\x00";
+        assert_eq!(inflate(data, None).unwrap().0, [b'a'; 1000]);
+        assert_eq!(inflate(data, Some(10)).unwrap().0, [b'a'; 10]);
+        assert_eq!(inflate(data, Some(0)).unwrap().0, b"");
+        assert_eq!(
+            inflate(b"\x01\x03\x00\xfc\xff\x61\x62\x63", Some(2))
+                .unwrap()
+                .0,
+            b"ab"
+        );
+        assert_eq!(gunzip(HI_GZ, Some(2)).unwrap(), b"hi");
+        assert_eq!(gunzip(HI_GZ, Some(3)).unwrap(), b"hi\n");
+        assert_eq!(gunzip(HI_GZ, Some(100)).unwrap(), b"hi\n");
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        assert_eq!(crc32(b"hi\n"), 0xed6f7a7a);
+    }
+
+    #[test]
+    fn gzip_members() {
+        assert!(is_gzip(HI_GZ));
+        assert!(!is_gzip(b"hi"));
+        assert_eq!(gunzip(HI_GZ, None).unwrap(), b"hi\n");
+        assert_eq!(gunzip(&[HI_GZ, HI_GZ].concat(), None).unwrap(), b"hi\nhi\n");
+        let empty =
+            b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\x03\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+        assert_eq!(gunzip(empty, None).unwrap(), b"");
+        assert_eq!(gunzip(b"", None).unwrap(), b"");
+
+        // with a filename and comment in the header
+        let mut named = HI_GZ[..10].to_vec();
+        named[3] = 0x08 | 0x10;
+        named.extend(b"hi.txt\0a comment\0");
+        named.extend(&HI_GZ[10..]);
+        assert_eq!(gunzip(&named, None).unwrap(), b"hi\n");
+    }
+
+    #[test]
+    fn bad_gzip() {
+        assert!(gunzip(b"not gzip", None).is_err());
+        assert!(gunzip(&HI_GZ[..HI_GZ.len() - 3], None).is_err());
+        let mut bad_crc = HI_GZ.to_vec();
+        bad_crc[15] ^= 1;
+        assert!(gunzip(&bad_crc, None).is_err());
+        let mut bad_size = HI_GZ.to_vec();
+        bad_size[19] = 4;
+        assert!(gunzip(&bad_size, None).is_err());
+        // trailing garbage after a member
+        assert!(gunzip(&[HI_GZ, b"junk"].concat(), None).is_err());
+        let mut unterminated = HI_GZ[..10].to_vec();
+        unterminated[3] = 0x08;
+        unterminated.extend(b"no nul");
+        assert!(gunzip(&unterminated, None).is_err());
+    }
+}
@@ -1,140 +1,1819 @@
-use std::fmt;
-use std::io;
-use std::path::Path;
+use std::collections::hash_map::{Entry, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::time::Duration;
 
+mod archive;
+mod color;
+mod config;
+mod decompress;
+mod encoding;
+mod glob;
+#[cfg(feature = "highlight")]
+mod highlight;
+mod http;
+mod human;
+mod ignore;
+mod inflate;
+mod meta;
+mod output;
+mod pager;
+mod paths;
+mod regex;
+mod size;
+mod template;
+mod term;
+mod text;
+mod time;
+mod walk;
+mod watch;
+mod width;
+
+use text::Text;
+
+/// Something to dump
 #[derive(Debug)]
-enum Text {
-    /// A single line of text. Will *not* contain a trailing newline
-    Oneline(String),
-    /// More than one line of text. Will contain a trailing newline
-    Multiline(String),
-    /// An error reading the file
-    Err(io::Error),
-}
-
-impl Text {
-    /// Process a text file's contents, figuring out single-line
-    fn new(mut text: String) -> Text {
-        fn count_newlines(s: &str) -> usize {
-            s.bytes().filter(|b| *b == b'\n').count()
-        }
-
-        let trimmed = text.trim_end_matches('\n');
-        let orig_count = count_newlines(&text);
-        let trimmed_count = count_newlines(trimmed);
-
-        if orig_count == 0 {
-            // no newlines in original string
-            Text::Oneline(text)
-        } else if trimmed_count == 0 {
-            // no newlines after we trim the trailing ones
-            text.truncate(trimmed.len());
-            Text::Oneline(text)
-        } else {
-            // inner newlines so this is a multi-line string. Make sure there's a trailing one
-            if !text.ends_with('\n') {
-                text.push('\n');
-            }
-            Text::Multiline(text)
+enum Input {
+    /// A local path or URL, read when it's dumped
+    Path(String),
+    /// Contents which have already been read, like an archive member
+    Loaded { label: String, data: Vec<u8> },
+    /// An argument which couldn't be expanded into files
+    Failed { label: String, err: io::Error },
+    /// A local path which was also given under other names, for `--dedupe`
+    Aliased { path: String, aliases: Vec<String> },
+}
+
+impl Input {
+    /// List a directory, adding an input for each file found, or an error if it can't be read.
+    /// Files in the current directory are labeled without a `./` prefix.
+    fn extend_dir(inputs: &mut Vec<Input>, dir: String, opts: &walk::Options) {
+        match walk::list_files(Path::new(&dir), opts) {
+            Ok(list) if dir == "." => inputs.extend(list.into_iter().map(Input::Path)),
+            Ok(list) => inputs.extend(list.into_iter().map(|rel| {
+                let path = Path::new(&dir).join(rel);
+                Input::Path(path.into_os_string().into_string().unwrap())
+            })),
+            Err(err) => inputs.push(Input::Failed { label: dir, err }),
+        }
+    }
+
+    /// Read all of an inherited file descriptor, which is left open.
+    fn read_fd(fd: i32, label: String, opts: &text::ReadOptions) -> Input {
+        Input::loaded(label, read_fd(fd, opts.read_limit()))
+    }
+
+    /// Read all of stdin.
+    fn read_stdin(label: String, opts: &text::ReadOptions) -> Input {
+        let mut data = Vec::new();
+        let mut stdin = io::stdin().lock();
+        let res = match opts.read_limit() {
+            Some(limit) => stdin.take(limit).read_to_end(&mut data),
+            None => stdin.read_to_end(&mut data),
+        };
+        Input::loaded(label, res.map(|_| data))
+    }
+
+    fn loaded(label: String, data: io::Result<Vec<u8>>) -> Input {
+        match data {
+            Ok(data) => Input::Loaded { label, data },
+            Err(err) => Input::Failed { label, err },
         }
     }
 
-    /// Read the contents of a file, and strip the terminating newline if exactly one exists.
-    pub fn read(path: impl AsRef<Path>) -> Text {
-        match std::fs::read_to_string(path) {
-            Ok(text) => Text::new(text),
-            Err(err) => Text::Err(err),
+    /// Read an archive, adding an input for each member, or an error if it can't be read.
+    fn extend_archive(inputs: &mut Vec<Input>, archive: &str, member: Option<&str>) {
+        match archive::read(archive, member) {
+            Ok(members) => inputs.extend(members.into_iter().map(|m| {
+                let label = format!("{archive}:{}", m.path);
+                match m.data {
+                    Ok(data) => Input::Loaded { label, data },
+                    Err(err) => Input::Failed { label, err },
+                }
+            })),
+            Err(err) => inputs.push(Input::Failed {
+                label: member.map_or_else(|| archive.to_owned(), |m| format!("{archive}:{m}")),
+                err,
+            }),
         }
     }
+}
+
+#[cfg(unix)]
+fn read_fd(fd: i32, limit: Option<u64>) -> io::Result<Vec<u8>> {
+    use std::mem::ManuallyDrop;
+    use std::os::raw::c_int;
+    use std::os::unix::io::FromRawFd;
+
+    const F_GETFD: c_int = 1;
+    extern "C" {
+        fn fcntl(fd: c_int, cmd: c_int, ...) -> c_int;
+    }
 
-    #[inline]
-    pub fn is_multiline(&self) -> bool {
-        matches!(self, Text::Multiline(_))
+    // SAFETY: F_GETFD only checks that fd is open. It's parsed from the command line before tabby
+    // opens any files itself, so it can only be one inherited from the parent process, and
+    // ManuallyDrop keeps it from being closed in case it's also stdin or the like.
+    if unsafe { fcntl(fd, F_GETFD) } < 0 {
+        return Err(io::Error::last_os_error());
     }
+    let file = ManuallyDrop::new(unsafe { File::from_raw_fd(fd) });
+    let mut data = Vec::new();
+    match limit {
+        Some(limit) => (&*file).take(limit).read_to_end(&mut data)?,
+        None => (&*file).read_to_end(&mut data)?,
+    };
+    Ok(data)
+}
+
+#[cfg(not(unix))]
+fn read_fd(_fd: i32, _limit: Option<u64>) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "file descriptors can only be read on unix",
+    ))
+}
+
+/// Something which identifies a file, regardless of the path used to reach it
+#[cfg(unix)]
+type FileId = (u64, u64);
+#[cfg(not(unix))]
+type FileId = PathBuf;
 
-    #[inline]
-    pub fn is_err(&self) -> bool {
-        matches!(self, Text::Err(_))
+fn file_id(path: &str, symlinks: walk::Symlinks) -> Option<FileId> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        // with --links=show, symlinks are shown as themselves, so they aren't aliases of their targets
+        let md = match symlinks {
+            walk::Symlinks::Show => fs::symlink_metadata(path),
+            _ => fs::metadata(path),
+        };
+        md.ok().map(|md| (md.dev(), md.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = symlinks;
+        fs::canonicalize(path).ok()
     }
 }
 
-impl fmt::Display for Text {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Text::Oneline(s) | Text::Multiline(s) => f.pad(s),
-            Text::Err(err) => write!(f, "[Error: {err}]"),
+/// Merge local paths which refer to the same file, by symlinks, hardlinks, or just being given
+/// twice, keeping the first one.
+fn dedupe(inputs: Vec<Input>, symlinks: walk::Symlinks) -> Vec<Input> {
+    let mut seen: HashMap<FileId, usize> = HashMap::new();
+    let mut out: Vec<(Input, Vec<String>)> = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        if let Input::Path(path) = &input {
+            if let Some(id) = (!http::is_url(path))
+                .then(|| file_id(path, symlinks))
+                .flatten()
+            {
+                match seen.entry(id) {
+                    Entry::Occupied(ent) => {
+                        let (first, aliases) = &mut out[*ent.get()];
+                        // the same path given twice is simply dropped
+                        if !matches!(first, Input::Path(p) if p == path) && !aliases.contains(path)
+                        {
+                            aliases.push(path.clone());
+                        }
+                        continue;
+                    }
+                    Entry::Vacant(ent) => {
+                        ent.insert(out.len());
+                    }
+                }
+            }
         }
+        out.push((input, Vec::new()));
     }
+
+    out.into_iter()
+        .map(|(input, aliases)| match input {
+            Input::Path(path) if !aliases.is_empty() => Input::Aliased { path, aliases },
+            input => input,
+        })
+        .collect()
 }
 
-fn parse_args() -> Vec<String> {
+/// Shuffle inputs randomly, with a generator seeded from the standard library's per-process
+/// random hash keys.
+fn shuffle<T>(items: &mut [T]) {
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut state = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    // splitmix64, which is plenty random for picking samples
+    let mut next = || {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    };
+    // Fisher-Yates
+    for i in (1..items.len()).rev() {
+        let j = (next() % (i as u64 + 1)) as usize;
+        items.swap(i, j);
+    }
+}
+
+/// Settings from the command line
+#[derive(Debug)]
+struct Args {
+    files: Vec<Input>,
+    symlinks: walk::Symlinks,
+    /// Decompress compressed files
+    decompress: bool,
+    /// Keep running and re-dump files when they change
+    watch: bool,
+    /// How often to check for changes in watch mode
+    interval: Duration,
+    /// Only show files which couldn't be read
+    errors_only: bool,
+    /// Only show the first lines of multiline files
+    lines: Option<usize>,
+    /// Only show the last lines of multiline files
+    tail: Option<usize>,
+    order: output::Order,
+    paths: paths::Style,
+    /// Remove this prefix from paths, or the directory they're all in if it's empty
+    strip_prefix: Option<String>,
+    /// Write the output here instead of stdout
+    output_file: Option<PathBuf>,
+    /// Append to `output_file` rather than replacing it
+    append: bool,
+    /// Create `output_file`'s parent directories
+    mkdir: bool,
+    /// Write each file's output to its own file under this directory
+    split_to: Option<PathBuf>,
+    /// Finish with a count of the files shown
+    summary: bool,
+    paging: pager::Paging,
+    read_opts: text::ReadOptions,
+    output: output::Options,
+}
+
+fn parse_args() -> Args {
     use clap::{Arg, ArgAction, ArgGroup};
 
-    let mut m = clap::command!()
+    let cmd = clap::command!()
+        .args_override_self(true)
+        .after_help(
+            "Default arguments can be set in $TABBY_CONFIG or ~/.config/tabby/config, one option \
+             per line, and are replaced by the same options on the command line.",
+        )
+        .arg(
+            Arg::new("no_config")
+                .long("no-config")
+                .action(ArgAction::SetTrue)
+                .help("Don't read default arguments from the config file"),
+        )
         .arg(
             Arg::new("all")
                 .short('a')
                 .long("all")
+                .action(ArgAction::Append)
+                .value_name("DIR")
+                .num_args(0..=1)
+                .default_missing_value(".")
+                .help("Dump all files in DIR, or the current directory (may be repeated)"),
+        )
+        .arg(
+            Arg::new("recursive")
+                .short('r')
+                .long("recursive")
                 .action(ArgAction::SetTrue)
-                .help("Dump all files in the current directory"),
+                .help("Descend into subdirectories, starting from the current directory if no FILE is given"),
+        )
+        .arg(
+            Arg::new("max_depth")
+                .long("max-depth")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .help("When recursing, only list files up to N levels deep"),
         )
         .arg(
             Arg::new("files")
                 .action(ArgAction::Append)
                 .value_name("FILE")
-                .help("File(s) to dump"),
+                .help("File(s) to dump, or - for stdin"),
+        )
+        .arg(
+            Arg::new("archive")
+                .long("archive")
+                .action(ArgAction::Append)
+                .value_name("ARCHIVE")
+                .help("Dump every file inside ARCHIVE, even if it's not named like a tar or zip file"),
+        )
+        .arg(
+            Arg::new("no_glob")
+                .long("no-glob")
+                .action(ArgAction::SetTrue)
+                .help("Don't expand glob patterns in FILE arguments"),
+        )
+        .arg(
+            Arg::new("stdin_names")
+                .long("stdin-names")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["all", "recursive"])
+                .help("Read additional filenames from stdin, one per line"),
+        )
+        .arg(
+            Arg::new("files_from")
+                .long("files-from")
+                .action(ArgAction::Append)
+                .value_name("LIST")
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all(["all", "recursive"])
+                .help("Read additional filenames from LIST, one per line ('-' for stdin)"),
+        )
+        .arg(
+            Arg::new("stdin_label")
+                .long("stdin-label")
+                .value_name("LABEL")
+                .default_value("(stdin)")
+                .help("Label for the contents of stdin, given as the FILE '-'"),
+        )
+        .arg(
+            Arg::new("fd")
+                .long("fd")
+                .action(ArgAction::Append)
+                .value_name("N[:LABEL]")
+                .value_parser(parse_fd)
+                .help("Dump the contents of the inherited file descriptor N, labeled as LABEL"),
+        )
+        .arg(
+            Arg::new("null")
+                .short('0')
+                .long("null")
+                .action(ArgAction::SetTrue)
+                .help("Filenames read by --stdin-names or --files-from are NUL-terminated"),
+        )
+        .arg(
+            Arg::new("binary")
+                .long("binary")
+                .value_name("HOW")
+                .value_parser(["skip", "placeholder", "hex", "raw"])
+                .default_value("placeholder")
+                .help(
+                    "How to show binary files: skip them, a placeholder with their size, a hexdump \
+                     of the start, or their raw bytes",
+                ),
+        )
+        .arg(
+            Arg::new("lossy")
+                .long("lossy")
+                .action(ArgAction::SetTrue)
+                .help("Show files which aren't valid UTF-8 as text, replacing invalid bytes with �"),
+        )
+        .arg(
+            Arg::new("encoding")
+                .long("encoding")
+                .value_name("ENCODING")
+                .value_parser(encoding::parse)
+                .default_value("utf-8")
+                .help(
+                    "Read files in ENCODING: utf-8, latin1, windows-1252, utf-16, utf-16le, \
+                     utf-16be, or auto to guess from a byte order mark or the contents",
+                ),
+        )
+        .arg(
+            Arg::new("crlf")
+                .long("crlf")
+                .value_name("HOW")
+                .value_parser(["strip", "keep", "show"])
+                .default_value("keep")
+                .help(
+                    "What to do with Windows line endings: change them to plain newlines, keep \
+                     them, or show them as ^M",
+                ),
+        )
+        .arg(
+            Arg::new("strip_ansi")
+                .long("strip-ansi")
+                .action(ArgAction::SetTrue)
+                .help("Remove ANSI escape sequences, like colors, from the contents of files"),
+        )
+        .arg(
+            Arg::new("trim")
+                .long("trim")
+                .value_name("HOW")
+                .value_parser(["none", "end", "both", "all-lines"])
+                .default_value("none")
+                .help(
+                    "Trim whitespace from the contents of files: none beyond trailing newlines, \
+                     all of it at the end, at both ends (keeping multiline files' indentation), \
+                     or at both ends and the end of every line",
+                ),
+        )
+        .arg(
+            Arg::new("squeeze_blank")
+                .short('s')
+                .long("squeeze-blank")
+                .action(ArgAction::SetTrue)
+                .help("Collapse runs of blank lines in multiline files into one, like cat -s"),
+        )
+        .arg(
+            Arg::new("show_nonprinting")
+                .long("show-nonprinting")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Show control characters like ^G, and bytes which aren't text like M-x, as \
+                     cat -v does",
+                ),
+        )
+        .arg(
+            Arg::new("show_all")
+                .short('A')
+                .long("show-all")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Show tabs as ^I and line ends as $, along with --show-nonprinting, like cat -A, \
+                     to find trailing whitespace",
+                ),
+        )
+        .arg(
+            Arg::new("expand_tabs")
+                .long("expand-tabs")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("8")
+                .help("Convert tabs in multiline files to spaces, with tab stops every N columns (8)"),
+        )
+        .arg(
+            Arg::new("read_special")
+                .long("read-special")
+                .action(ArgAction::SetTrue)
+                .help("Try a bounded, non-blocking read of FIFOs, sockets, and device nodes"),
+        )
+        .arg(
+            Arg::new("watch")
+                .short('w')
+                .long("watch")
+                .action(ArgAction::SetTrue)
+                .help("Keep running, and dump the files again when they change"),
+        )
+        .arg(
+            Arg::new("interval")
+                .long("interval")
+                .value_name("SECS")
+                .value_parser(parse_interval)
+                .default_value("2")
+                .help("How often --watch checks files which don't report changes, like those in /proc and /sys"),
+        )
+        .arg(
+            Arg::new("no_decompress")
+                .long("no-decompress")
+                .action(ArgAction::SetTrue)
+                .help("Don't decompress gzip, xz, zstd, and bzip2 files"),
+        )
+        .arg(
+            Arg::new("max_size")
+                .long("max-size")
+                .value_name("SIZE")
+                .value_parser(size::parse)
+                .help("Skip files larger than SIZE bytes (suffixes like K, M, and G are allowed)"),
+        )
+        .arg(
+            Arg::new("truncate")
+                .long("truncate")
+                .action(ArgAction::SetTrue)
+                .help("Show the first --max-size bytes of large files instead of skipping them"),
+        )
+        .arg(
+            Arg::new("min_file_size")
+                .long("min-file-size")
+                .value_name("SIZE")
+                .value_parser(size::parse)
+                .help("Only dump files of at least SIZE bytes in --all/--recursive mode"),
+        )
+        .arg(
+            Arg::new("max_file_size")
+                .long("max-file-size")
+                .value_name("SIZE")
+                .value_parser(size::parse)
+                .help("Only dump files of at most SIZE bytes in --all/--recursive mode"),
+        )
+        .arg(
+            Arg::new("exclude")
+                .long("exclude")
+                .action(ArgAction::Append)
+                .value_name("PATTERN")
+                .help("Skip files and directories matching the glob PATTERN in --all/--recursive mode"),
+        )
+        .arg(
+            Arg::new("exclude_from")
+                .long("exclude-from")
+                .action(ArgAction::Append)
+                .value_name("FILE")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Read exclude patterns from FILE, one per line"),
+        )
+        .arg(
+            Arg::new("shuffle")
+                .long("shuffle")
+                .action(ArgAction::SetTrue)
+                .help("Dump files in a random order, before applying --limit"),
+        )
+        .arg(
+            Arg::new("limit")
+                .long("limit")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Only dump the first N files, after sorting and filtering"),
+        )
+        .arg(
+            Arg::new("errors_only")
+                .long("errors-only")
+                .action(ArgAction::SetTrue)
+                .help("Only show files which couldn't be read"),
+        )
+        .arg(
+            Arg::new("dedupe")
+                .long("dedupe")
+                .action(ArgAction::SetTrue)
+                .help("Dump files given more than once, or by several names, only once"),
+        )
+        .arg(
+            Arg::new("follow")
+                .short('L')
+                .long("follow")
+                .action(ArgAction::SetTrue)
+                .overrides_with_all(["no_follow", "links"])
+                .help("Follow symlinks found in --all/--recursive mode, the same as --links=follow"),
+        )
+        .arg(
+            Arg::new("no_follow")
+                .long("no-follow")
+                .action(ArgAction::SetTrue)
+                .overrides_with_all(["follow", "links"])
+                .help("Never follow symlinks, show their targets instead, the same as --links=show"),
         )
+        .arg(
+            Arg::new("links")
+                .long("links")
+                .value_name("MODE")
+                .value_parser(["skip", "follow", "show"])
+                .overrides_with_all(["follow", "no_follow"])
+                .help(
+                    "How to treat symlinks: skip those found in --all/--recursive mode but follow \
+                     those given as FILEs (the default), always follow them, or show them as \
+                     path -> target without following them",
+                ),
+        )
+        .arg(
+            Arg::new("hidden")
+                .long("hidden")
+                .action(ArgAction::SetTrue)
+                .overrides_with("no_hidden")
+                .help("Include hidden files in --all/--recursive mode (the default)"),
+        )
+        .arg(
+            Arg::new("no_hidden")
+                .long("no-hidden")
+                .action(ArgAction::SetTrue)
+                .overrides_with("hidden")
+                .help("Skip hidden files and directories in --all/--recursive mode"),
+        )
+        .arg(
+            Arg::new("ext")
+                .long("ext")
+                .action(ArgAction::Append)
+                .value_name("EXT")
+                .value_delimiter(',')
+                .help("Only dump files with these extensions in --all/--recursive mode"),
+        )
+        .arg(
+            Arg::new("name")
+                .long("name")
+                .action(ArgAction::Append)
+                .value_name("REGEX")
+                .value_parser(|s: &str| regex::Regex::new(s))
+                .help("Only dump files whose names match REGEX in --all/--recursive mode"),
+        )
+        .arg(
+            Arg::new("type")
+                .long("type")
+                .action(ArgAction::Append)
+                .value_name("TYPE")
+                .value_delimiter(',')
+                .value_parser(["f", "l", "d", "p", "s", "c", "b"])
+                .help(
+                    "Only dump entries of these types in --all/--recursive mode: f (regular \
+                     files), l (symlinks), d (directories), p (fifos), s (sockets), c (character \
+                     devices), or b (block devices)",
+                ),
+        )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .value_name("KEY")
+                .value_parser(["name", "size", "mtime", "value", "none"])
+                .default_value("name")
+                .help(
+                    "Order of files found in --all/--recursive mode, or of the output by contents \
+                     with the largest numbers first if KEY is value (instead of --order)",
+                ),
+        )
+        .arg(
+            Arg::new("reverse")
+                .long("reverse")
+                .action(ArgAction::SetTrue)
+                .help("Reverse the --sort order, so that --sort value puts the smallest numbers first"),
+        )
+        .arg(
+            Arg::new("newer")
+                .long("newer")
+                .value_name("TIME")
+                .value_parser(time::parse_time)
+                .help(
+                    "Only dump files modified after TIME in --all/--recursive mode, where TIME \
+                     is a duration ago (30m, 2d12h), @SECONDS, or YYYY-MM-DD[THH:MM[:SS]]",
+                ),
+        )
+        .arg(
+            Arg::new("older")
+                .long("older")
+                .value_name("TIME")
+                .value_parser(time::parse_time)
+                .help("Only dump files modified before TIME in --all/--recursive mode"),
+        )
+        .arg(
+            Arg::new("gitignore")
+                .long("gitignore")
+                .action(ArgAction::SetTrue)
+                .help("Skip files ignored by git in --all/--recursive mode"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .action(ArgAction::SetTrue)
+                .help("Print a JSON array of objects with path, content, multiline, and error fields"),
+        )
+        .arg(
+            Arg::new("jsonl")
+                .long("jsonl")
+                .action(ArgAction::SetTrue)
+                .help("Print a JSON object for each file on its own line, as soon as it's read"),
+        )
+        .arg(
+            Arg::new("yaml")
+                .long("yaml")
+                .action(ArgAction::SetTrue)
+                .help("Print a YAML mapping of paths to contents"),
+        )
+        .arg(
+            Arg::new("toml")
+                .long("toml")
+                .action(ArgAction::SetTrue)
+                .help("Print TOML key/value pairs of paths and contents"),
+        )
+        .arg(
+            Arg::new("csv")
+                .long("csv")
+                .action(ArgAction::SetTrue)
+                .help("Print CSV rows of path, content, is_multiline, and error"),
+        )
+        .arg(
+            Arg::new("tsv")
+                .long("tsv")
+                .action(ArgAction::SetTrue)
+                .help("Print tab-separated rows like --csv, escaping tabs and newlines as \\t and \\n"),
+        )
+        .arg(
+            Arg::new("separator")
+                .long("separator")
+                .value_name("STRING")
+                .value_parser(|s: &str| Ok::<_, String>(unescape(s)))
+                .default_value(": ")
+                .help("Put STRING between paths and contents (escapes like \\t are allowed)"),
+        )
+        .arg(
+            Arg::new("align")
+                .long("align")
+                .value_name("HOW")
+                .value_parser(["right", "left", "values", "none"])
+                .default_value("right")
+                .help(
+                    "How to line up one-line files: right- or left-align paths, left-align paths \
+                     and line up values, or neither",
+                ),
+        )
+        .arg(
+            Arg::new("align_values")
+                .long("align-values")
+                .action(ArgAction::SetTrue)
+                .help("Line up values in a column of their own, the same as --align=values"),
+        )
+        .arg(
+            Arg::new("long")
+                .short('l')
+                .long("long")
+                .action(ArgAction::SetTrue)
+                .help("Show each file's permissions, owner, size, and modification time"),
+        )
+        .arg(
+            Arg::new("time_style")
+                .long("time-style")
+                .value_name("STYLE")
+                .value_parser(time::parse_style)
+                .help(
+                    "How to show modification times: default, iso, relative (like 3m ago), epoch, \
+                     or +FORMAT with strftime conversions like %Y-%m-%d",
+                ),
+        )
+        .arg(
+            Arg::new("perms")
+                .long("perms")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("long")
+                .help("Show each file's mode, in octal and like ls -l, and its owner and group"),
+        )
+        .arg(
+            Arg::new("context")
+                .long("context")
+                .action(ArgAction::SetTrue)
+                .help("Show each file's SELinux security context, like ls -Z"),
+        )
+        .arg(
+            Arg::new("xattrs")
+                .long("xattrs")
+                .action(ArgAction::SetTrue)
+                .help("List each file's extended attributes and their values under it, or as JSON fields"),
+        )
+        .arg(
+            Arg::new("number")
+                .short('n')
+                .long("number")
+                .action(ArgAction::SetTrue)
+                .help("Number the lines of multiline files"),
+        )
+        .arg(
+            Arg::new("lines")
+                .long("lines")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Only show the first N lines of multiline files"),
+        )
+        .arg(
+            Arg::new("tail")
+                .long("tail")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .conflicts_with("lines")
+                .help("Only show the last N lines of multiline files"),
+        )
+        .arg(
+            Arg::new("max_width")
+                .long("max-width")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help(
+                    "Truncate one-line files to fit in N columns, or 0 for no limit \
+                     [default: terminal width]",
+                ),
+        )
+        .arg(
+            Arg::new("wrap")
+                .long("wrap")
+                .action(ArgAction::SetTrue)
+                .help("Wrap long one-line files onto more lines instead of truncating them"),
+        )
+        .arg(
+            Arg::new("info")
+                .long("info")
+                .action(ArgAction::SetTrue)
+                .help("Show how many lines and bytes are in each file"),
+        )
+        .arg(
+            Arg::new("quote")
+                .long("quote")
+                .value_name("STYLE")
+                .value_parser(["shell"])
+                .help("Quote contents so that they can be pasted into a shell"),
+        )
+        .arg(
+            Arg::new("highlight")
+                .long("highlight")
+                .value_name("REGEX")
+                .value_parser(regex::Regex::new)
+                .help("Color matches of REGEX within the contents of files, when colors are enabled"),
+        )
+        .arg(
+            Arg::new("error_format")
+                .long("error-format")
+                .value_name("TEMPLATE")
+                .value_parser(|s: &str| template::Template::parse(&unescape(s)))
+                .help(
+                    "Show files which couldn't be read with TEMPLATE instead of [Error: ...], using \
+                     the --format placeholders, or leave them out entirely if TEMPLATE is empty",
+                ),
+        )
+        .arg(
+            Arg::new("empty_placeholder")
+                .long("empty-placeholder")
+                .value_name("STRING")
+                .default_value("<empty>")
+                .help("What to show for empty files in the text and table formats"),
+        )
+        .arg(
+            Arg::new("human")
+                .long("human")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Show numbers from sysfs in their units too, like 48230000 (48.2 Wh) for a \
+                     battery's energy_now, in the text and table formats",
+                ),
+        )
+        .arg(
+            Arg::new("preview")
+                .long("preview")
+                .action(ArgAction::SetTrue)
+                .help("Show just the first line of multiline files, in line with the one-line files"),
+        )
+        .arg(
+            Arg::new("grid")
+                .long("grid")
+                .action(ArgAction::SetTrue)
+                .help("Pack one-line files into as many columns as fit across the terminal, like ls"),
+        )
+        .arg(
+            Arg::new("leaders")
+                .long("leaders")
+                .action(ArgAction::SetTrue)
+                .help("Left-align paths and fill the space up to their values with dots"),
+        )
+        .arg(
+            Arg::new("dedupe_content")
+                .long("dedupe-content")
+                .action(ArgAction::SetTrue)
+                .help("Print the contents of identical multiline files once, under all of their paths"),
+        )
+        .arg(
+            Arg::new("icons")
+                .long("icons")
+                .action(ArgAction::SetTrue)
+                .help("Start each file with a symbol: ✓ read, ✗ error, ≡ multiline, or ⌁ binary"),
+        )
+        .arg(
+            Arg::new("heading_style")
+                .long("heading-style")
+                .value_name("STYLE")
+                .value_parser(parse_heading_style)
+                .default_value("colon")
+                .help(
+                    "How to introduce multiline files: colon (path:), tail (==> path <==), \
+                     comment (# path), or a template like --format's",
+                ),
+        )
+        .arg(
+            Arg::new("indent")
+                .long("indent")
+                .value_name("N|PREFIX")
+                .value_parser(parse_indent)
+                .help("Indent multiline files by N spaces, or put PREFIX before each line"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_name("TEMPLATE")
+                .value_parser(|s: &str| template::Template::parse(&unescape(s)))
+                .help(
+                    "Print each file with TEMPLATE, which can use {path}, {basename}, {content}, \
+                     {size}, {mtime}, and for files which couldn't be read, {error} and {errno}",
+                ),
+        )
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .value_name("WHEN")
+                .value_parser(["auto", "always", "never"])
+                .default_value("auto")
+                .default_missing_value("always")
+                .num_args(0..=1)
+                .require_equals(true)
+                .help("When to use colors in the text output: auto (on a terminal), always, or never"),
+        )
+        .arg(
+            Arg::new("theme")
+                .long("theme")
+                .value_name("THEME")
+                .value_parser(color::THEMES)
+                .default_value("dark")
+                .help("Colors to use: dark or light for the terminal's background, or solarized"),
+        )
+        .arg(
+            Arg::new("colors")
+                .long("colors")
+                .value_name("SPEC")
+                .value_parser(|spec: &str| color::Palette::default().set(spec).map(|()| spec.to_owned()))
+                .action(ArgAction::Append)
+                .help(
+                    "Override the theme's colors with a list like path=1;34:number=33, where the \
+                     parts are path, header, error, match, and the values: value, number, \
+                     boolean, empty, and placeholder. Styles are ANSI SGR parameters",
+                ),
+        )
+        .arg(
+            Arg::new("paths")
+                .long("paths")
+                .value_name("STYLE")
+                .value_parser(["relative", "absolute", "basename"])
+                .help("Show paths relative to the current directory, absolute, or as just filenames"),
+        )
+        .arg(
+            Arg::new("strip_prefix")
+                .long("strip-prefix")
+                .value_name("PREFIX")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("")
+                .help("Remove PREFIX from the start of paths, or the directory they're all in if not given"),
+        )
+        .arg(
+            Arg::new("order")
+                .long("order")
+                .value_name("KEY")
+                .value_parser(["cli", "name", "value"])
+                .default_value("cli")
+                .help("Order of the output: as given, or sorted by path or by contents"),
+        )
+        .arg(
+            Arg::new("summary")
+                .long("summary")
+                .action(ArgAction::SetTrue)
+                .help("Finish with a count of the files shown and their total size"),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with("watch")
+                .help("Write the output to PATH instead of stdout, replacing it once it's complete"),
+        )
+        .arg(
+            Arg::new("append")
+                .long("append")
+                .action(ArgAction::SetTrue)
+                .requires("output")
+                .help("Append to the --output file instead of replacing it"),
+        )
+        .arg(
+            Arg::new("mkdir")
+                .long("mkdir")
+                .action(ArgAction::SetTrue)
+                .requires("output")
+                .help("Create the --output file's parent directories if needed"),
+        )
+        .arg(
+            Arg::new("split_to")
+                .long("split-to")
+                .value_name("DIR")
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all(["output", "watch"])
+                .help("Write each file's output to its own file under DIR, at the same path"),
+        )
+        .arg(
+            Arg::new("paging")
+                .long("paging")
+                .value_name("WHEN")
+                .value_parser(["auto", "always", "never"])
+                .default_value("auto")
+                .help("When to show the output in $PAGER: auto (if taller than the terminal), always, or never"),
+        )
+        .arg(
+            Arg::new("table")
+                .long("table")
+                .value_name("STYLE")
+                .value_parser(["plain", "box"])
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("plain")
+                .help("Print a table with a row per file, with box-drawing borders if STYLE is box"),
+        )
+        .arg(
+            Arg::new("markdown")
+                .long("markdown")
+                .action(ArgAction::SetTrue)
+                .help("Print each file as a Markdown heading and code block"),
+        )
+        .arg(
+            Arg::new("html")
+                .long("html")
+                .action(ArgAction::SetTrue)
+                .help("Print a standalone HTML page, with a section for each file"),
+        )
+        .arg(
+            Arg::new("print0")
+                .long("print0")
+                .action(ArgAction::SetTrue)
+                .help("Print each path and its contents followed by a NUL byte"),
+        )
+        .arg(
+            Arg::new("no_header")
+                .long("no-header")
+                .visible_alias("bare")
+                .action(ArgAction::SetTrue)
+                .help("Print only the contents of each file, in order, without paths"),
+        )
+        .arg(
+            Arg::new("tree")
+                .long("tree")
+                .action(ArgAction::SetTrue)
+                .help("Print paths as a tree of directories, with each file's contents beside it"),
+        )
+        .arg(
+            Arg::new("group_by_value")
+                .long("group-by-value")
+                .action(ArgAction::SetTrue)
+                .help("Print each distinct content once, followed by the paths of the files holding it"),
+        )
+        .arg(
+            Arg::new("env")
+                .long("env")
+                .action(ArgAction::SetTrue)
+                .help("Print one-line files as shell variable assignments, like NAME='contents'"),
+        )
+        .arg(
+            Arg::new("xml")
+                .long("xml")
+                .action(ArgAction::SetTrue)
+                .help("Print an XML document with a <file> element for each file"),
+        )
+        .arg(
+            Arg::new("org")
+                .long("org")
+                .action(ArgAction::SetTrue)
+                .help("Print each file as an org-mode heading and example block"),
+        )
+        .arg(
+            Arg::new("rec")
+                .long("rec")
+                .action(ArgAction::SetTrue)
+                .help("Print a GNU recutils record for each file, with Path and Content fields"),
+        )
+        .group(ArgGroup::new("output_format").args([
+            "json", "jsonl", "yaml", "toml", "csv", "tsv", "table", "markdown", "html", "print0",
+            "no_header", "tree", "group_by_value", "env", "xml", "org", "rec", "format",
+        ]))
         .group(
             ArgGroup::new("file_opts")
-                .args(["all", "files"])
+                .args(["all", "recursive", "files", "stdin_names", "files_from", "archive", "fd"])
+                .multiple(true)
                 .required(true),
+        );
+    let mut m = cmd.clone().get_matches_from(config::args_with_config(&cmd));
+
+    // only the real command line can conflict, since the config file's are replaced by it
+    if config::sort_conflicts(&cmd, &config::cli_args()) {
+        clap::Error::raw(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--sort value sorts the output, so it can't be used with --order\n",
         )
-        .get_matches();
-
-    if m.get_flag("all") {
-        std::fs::read_dir(".")
-            .expect("unable to read contents of current directory")
-            .filter_map(|res| res.ok())
-            .filter(|ent| ent.file_type().map(|ft| ft.is_file()).unwrap_or(false))
-            .map(|ent| {
-                ent.file_name()
-                    .into_string()
-                    .expect("filename is not UTF-8")
+        .exit();
+    }
+
+    let read_opts = text::ReadOptions {
+        read_special: m.get_flag("read_special"),
+        max_size: m.remove_one("max_size"),
+        truncate: m.get_flag("truncate"),
+        binary: match m.get_one::<String>("binary").unwrap().as_str() {
+            "skip" => text::Binary::Skip,
+            "hex" => text::Binary::Hex,
+            "raw" => text::Binary::Raw,
+            _ => text::Binary::Placeholder,
+        },
+        lossy: m.get_flag("lossy"),
+        encoding: m.remove_one("encoding").unwrap(),
+        crlf: match m.get_one::<String>("crlf").unwrap().as_str() {
+            "strip" => text::Crlf::Strip,
+            "show" => text::Crlf::Show,
+            _ => text::Crlf::Keep,
+        },
+        strip_ansi: m.get_flag("strip_ansi"),
+        trim: match m.get_one::<String>("trim").unwrap().as_str() {
+            "end" => text::Trim::End,
+            "both" => text::Trim::Both,
+            "all-lines" => text::Trim::AllLines,
+            _ => text::Trim::None,
+        },
+        squeeze_blank: m.get_flag("squeeze_blank"),
+        show_nonprinting: m.get_flag("show_nonprinting") || m.get_flag("show_all"),
+        show_all: m.get_flag("show_all"),
+        expand_tabs: m.get_one::<u64>("expand_tabs").map(|&n| n as usize),
+    };
+
+    let mut files = Vec::new();
+    let mut walk_opts = walk::Options {
+        recursive: m.get_flag("recursive"),
+        max_depth: m.remove_one::<u64>("max_depth").map(|n| n as usize),
+        exclude: m.remove_many("exclude").into_iter().flatten().collect(),
+        skip_hidden: m.get_flag("no_hidden"),
+        extensions: m
+            .remove_many::<String>("ext")
+            .into_iter()
+            .flatten()
+            .map(|ext| ext.trim_start_matches('.').to_owned())
+            .collect(),
+        names: m.remove_many("name").into_iter().flatten().collect(),
+        types: m
+            .remove_many::<String>("type")
+            .into_iter()
+            .flatten()
+            .map(|t| match t.as_str() {
+                "l" => walk::FileType::Symlink,
+                "d" => walk::FileType::Dir,
+                "p" => walk::FileType::Fifo,
+                "s" => walk::FileType::Socket,
+                "c" => walk::FileType::CharDevice,
+                "b" => walk::FileType::BlockDevice,
+                _ => walk::FileType::File,
             })
-            .collect()
+            .collect(),
+        gitignore: m.get_flag("gitignore"),
+        symlinks: match m.get_one::<String>("links").map(String::as_str) {
+            _ if m.get_flag("follow") => walk::Symlinks::Follow,
+            _ if m.get_flag("no_follow") => walk::Symlinks::Show,
+            Some("follow") => walk::Symlinks::Follow,
+            Some("show") => walk::Symlinks::Show,
+            _ => walk::Symlinks::Skip,
+        },
+        sort: match m.get_one::<String>("sort").unwrap().as_str() {
+            "size" => walk::Sort::Size,
+            "mtime" => walk::Sort::Mtime,
+            "none" => walk::Sort::None,
+            _ => walk::Sort::Name,
+        },
+        reverse: m.get_flag("reverse") && m.get_one::<String>("sort").unwrap() != "value",
+        newer: m.remove_one("newer"),
+        older: m.remove_one("older"),
+        min_size: m.remove_one("min_file_size"),
+        max_size: m.remove_one("max_file_size"),
+    };
+    for path in m
+        .remove_many::<PathBuf>("exclude_from")
+        .into_iter()
+        .flatten()
+    {
+        let patterns = File::open(&path).and_then(|f| read_names(io::BufReader::new(f), b'\n'));
+        walk_opts.exclude.extend(
+            patterns
+                .unwrap_or_else(|err| {
+                    eprintln!("tabby: unable to read {}: {err}", path.display());
+                    exit(1)
+                })
+                .into_iter()
+                .filter(|pat| !pat.starts_with('#')),
+        );
+    }
+
+    let mut args: Vec<String> = Vec::new();
+    if let Some(files) = m.remove_many::<String>("files") {
+        if m.get_flag("no_glob") {
+            args.extend(files);
+        } else {
+            args.extend(files.flat_map(|file| {
+                // like the shell, leave patterns that don't match anything untouched
+                match (glob::is_glob(&file) && !http::is_url(&file)).then(|| glob::expand(&file)) {
+                    Some(paths) if !paths.is_empty() => paths,
+                    _ => vec![file],
+                }
+            }));
+        }
+    }
+
+    let mut dirs: Vec<String> = m.remove_many("all").into_iter().flatten().collect();
+    if dirs.is_empty() && walk_opts.recursive && args.is_empty() {
+        dirs.push(".".to_owned());
+    }
+    for dir in dirs {
+        Input::extend_dir(&mut files, dir, &walk_opts);
+    }
+
+    let stdin_label = m.remove_one::<String>("stdin_label").unwrap();
+    for arg in args {
+        if http::is_url(&arg) {
+            files.push(Input::Path(arg));
+            continue;
+        }
+        if arg == "-" {
+            files.push(Input::read_stdin(stdin_label.clone(), &read_opts));
+            continue;
+        }
+        // /dev/fd/N would otherwise be skipped as a pipe, so read it like --fd
+        if let Some(fd) = arg.strip_prefix("/dev/fd/").and_then(|n| n.parse().ok()) {
+            files.push(Input::read_fd(fd, arg, &read_opts));
+            continue;
+        }
+        if let Some((archive, member)) = archive::split_member(&arg) {
+            Input::extend_archive(&mut files, archive, Some(member));
+            continue;
+        }
+        if archive::is_archive(&arg) {
+            Input::extend_archive(&mut files, &arg, None);
+            continue;
+        }
+
+        // directories expand to the files inside them, but symlinks aren't followed with --links=show
+        let is_link = || fs::symlink_metadata(&arg).is_ok_and(|md| md.is_symlink());
+        let is_dir = fs::metadata(&arg).is_ok_and(|md| md.is_dir())
+            && !(walk_opts.symlinks == walk::Symlinks::Show && is_link());
+        if is_dir {
+            Input::extend_dir(&mut files, arg, &walk_opts);
+        } else {
+            files.push(Input::Path(arg));
+        }
+    }
+
+    for archive in m.remove_many::<String>("archive").into_iter().flatten() {
+        Input::extend_archive(&mut files, &archive, None);
+    }
+
+    for (fd, label) in m
+        .remove_many::<(i32, Option<String>)>("fd")
+        .into_iter()
+        .flatten()
+    {
+        let label = label.unwrap_or_else(|| format!("/dev/fd/{fd}"));
+        files.push(Input::read_fd(fd, label, &read_opts));
+    }
+
+    let delim = if m.get_flag("null") { b'\0' } else { b'\n' };
+    if m.get_flag("stdin_names") {
+        let names = read_names(io::stdin().lock(), delim).unwrap_or_else(|err| {
+            eprintln!("tabby: unable to read filenames from stdin: {err}");
+            exit(1)
+        });
+        files.extend(names.into_iter().map(Input::Path));
+    }
+
+    for list in m.remove_many::<PathBuf>("files_from").into_iter().flatten() {
+        let names = if list.as_os_str() == "-" {
+            read_names(io::stdin().lock(), delim)
+        } else {
+            File::open(&list).and_then(|f| read_names(io::BufReader::new(f), delim))
+        };
+        let names = names.unwrap_or_else(|err| {
+            eprintln!("tabby: unable to read file list {}: {err}", list.display());
+            exit(1)
+        });
+        files.extend(names.into_iter().map(Input::Path));
+    }
+
+    if m.get_flag("dedupe") {
+        files = dedupe(files, walk_opts.symlinks);
+    }
+    if m.get_flag("shuffle") {
+        shuffle(&mut files);
+    }
+    if let Some(limit) = m.remove_one("limit") {
+        files.truncate(limit);
+    }
+
+    // colors and the terminal's width only matter for output to the terminal
+    let to_file = m.contains_id("output") || m.contains_id("split_to");
+    let use_color = match m.get_one::<String>("color").unwrap().as_str() {
+        "always" => color::When::Always,
+        "never" => color::When::Never,
+        _ if to_file => color::When::Never,
+        _ => color::When::Auto,
+    }
+    .enabled();
+
+    Args {
+        files,
+        symlinks: walk_opts.symlinks,
+        decompress: !m.get_flag("no_decompress"),
+        watch: m.get_flag("watch"),
+        interval: Duration::from_secs_f64(*m.get_one::<f64>("interval").unwrap()),
+        errors_only: m.get_flag("errors_only"),
+        lines: m.remove_one("lines"),
+        tail: m.remove_one("tail"),
+        summary: m.get_flag("summary"),
+        strip_prefix: m.remove_one("strip_prefix"),
+        paths: match m.get_one::<String>("paths").map(String::as_str) {
+            Some("relative") => paths::Style::Relative,
+            Some("absolute") => paths::Style::Absolute,
+            Some("basename") => paths::Style::Basename,
+            _ => paths::Style::Given,
+        },
+        output_file: m.get_one::<PathBuf>("output").cloned(),
+        append: m.get_flag("append"),
+        mkdir: m.get_flag("mkdir"),
+        split_to: m.get_one::<PathBuf>("split_to").cloned(),
+        order: match m.get_one::<String>("order").unwrap().as_str() {
+            // --sort value needs the contents, so it's applied to the output instead of the walk
+            _ if m.get_one::<String>("sort").unwrap() == "value" => {
+                if m.get_flag("reverse") {
+                    output::Order::Value
+                } else {
+                    output::Order::ValueDescending
+                }
+            }
+            "name" => output::Order::Name,
+            "value" => output::Order::Value,
+            _ => output::Order::Cli,
+        },
+        paging: match m.get_one::<String>("paging").unwrap().as_str() {
+            "always" => pager::Paging::Always,
+            "never" => pager::Paging::Never,
+            _ => pager::Paging::Auto,
+        },
+        read_opts,
+        output: output::Options {
+            format: if m.get_flag("json") {
+                output::Format::Json
+            } else if m.get_flag("jsonl") {
+                output::Format::Jsonl
+            } else if m.get_flag("yaml") {
+                output::Format::Yaml
+            } else if m.get_flag("toml") {
+                output::Format::Toml
+            } else if m.get_flag("csv") {
+                output::Format::Csv
+            } else if m.get_flag("tsv") {
+                output::Format::Tsv
+            } else if m.get_flag("markdown") {
+                output::Format::Markdown
+            } else if m.get_flag("html") {
+                output::Format::Html
+            } else if m.get_flag("print0") {
+                output::Format::Print0
+            } else if m.get_flag("no_header") {
+                output::Format::Bare
+            } else if m.get_flag("tree") {
+                output::Format::Tree
+            } else if m.get_flag("group_by_value") {
+                output::Format::Grouped
+            } else if m.get_flag("env") {
+                output::Format::Env
+            } else if m.get_flag("xml") {
+                output::Format::Xml
+            } else if m.get_flag("org") {
+                output::Format::Org
+            } else if m.get_flag("rec") {
+                output::Format::Rec
+            } else if let Some(style) = m.get_one::<String>("table") {
+                output::Format::Table {
+                    borders: style == "box",
+                }
+            } else {
+                output::Format::Text
+            },
+            separator: m.remove_one("separator").unwrap(),
+            align: match m.get_one::<String>("align").unwrap().as_str() {
+                _ if m.get_flag("align_values") => output::Align::Values,
+                "left" => output::Align::Left,
+                "values" => output::Align::Values,
+                "none" => output::Align::None,
+                _ => output::Align::Right,
+            },
+            template: m.remove_one("format"),
+            palette: if use_color {
+                let mut palette = color::Palette::theme(m.get_one::<String>("theme").unwrap());
+                for spec in m.get_many::<String>("colors").into_iter().flatten() {
+                    palette.set(spec).unwrap();
+                }
+                palette
+            } else {
+                color::Palette::plain()
+            },
+            highlight: use_color,
+            long: m.get_flag("long"),
+            perms: m.get_flag("perms"),
+            context: m.get_flag("context"),
+            xattrs: m.get_flag("xattrs"),
+            number: m.get_flag("number"),
+            indent: m.remove_one("indent").unwrap_or_default(),
+            max_width: match m.remove_one::<usize>("max_width") {
+                Some(0) => None,
+                Some(width) => Some(width),
+                None => term::size().filter(|_| !to_file).map(|(cols, _)| cols),
+            },
+            wrap: m.get_flag("wrap"),
+            heading_style: m.remove_one("heading_style").unwrap(),
+            info: m.get_flag("info"),
+            quote: m.get_one::<String>("quote").map(|_| output::Quote::Shell),
+            highlight_regex: m.remove_one("highlight").filter(|_| use_color),
+            icons: m.get_flag("icons"),
+            leaders: m.get_flag("leaders"),
+            grid: m.get_flag("grid"),
+            preview: m.get_flag("preview"),
+            error_format: m.remove_one("error_format"),
+            time_style: m.remove_one("time_style").unwrap_or_default(),
+            empty_placeholder: m.remove_one("empty_placeholder").unwrap(),
+            dedupe: m.get_flag("dedupe_content"),
+            human: m.get_flag("human"),
+        },
+    }
+}
+
+/// Expand backslash escapes like `\t` and `\n` in an argument. Unknown escapes are left as-is.
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('0') => out.push('\0'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+fn parse_heading_style(s: &str) -> Result<output::HeadingStyle, String> {
+    match s {
+        "colon" => Ok(output::HeadingStyle::Colon),
+        "tail" => Ok(output::HeadingStyle::Tail),
+        "comment" => Ok(output::HeadingStyle::Comment),
+        s if s.contains('{') => {
+            template::Template::parse(&unescape(s)).map(output::HeadingStyle::Template)
+        }
+        _ => Err("expected colon, tail, comment, or a template like '[{path}]'".to_owned()),
+    }
+}
+
+/// Parse `--indent`, which is either a number of spaces or a prefix string.
+fn parse_indent(s: &str) -> Result<String, String> {
+    match s.parse::<usize>() {
+        Ok(n) => Ok(" ".repeat(n)),
+        Err(_) => Ok(unescape(s)),
+    }
+}
+
+fn parse_fd(s: &str) -> Result<(i32, Option<String>), String> {
+    let (fd, label) = match s.split_once(':') {
+        Some((fd, label)) => (fd, Some(label.to_owned())),
+        None => (s, None),
+    };
+    match fd.parse::<i32>() {
+        Ok(fd) if fd >= 0 => Ok((fd, label)),
+        _ => Err(format!("'{fd}' is not a file descriptor number")),
+    }
+}
+
+fn parse_interval(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(secs) if secs > 0.0 && secs.is_finite() => Ok(secs),
+        _ => Err(format!("'{s}' is not a positive number of seconds")),
+    }
+}
+
+/// Read a list of filenames separated by `delim` (typically newline or NUL), skipping empty names.
+fn read_names(reader: impl BufRead, delim: u8) -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for name in reader.split(delim) {
+        let mut name = name?;
+        if delim == b'\n' && name.ends_with(b"\r") {
+            name.pop();
+        }
+        if !name.is_empty() {
+            names.push(String::from_utf8(name).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "filename is not UTF-8")
+            })?);
+        }
+    }
+    Ok(names)
+}
+
+/// Read an input, along with its metadata if it's a local file.
+fn read_input(input: &Input, args: &Args) -> output::Entry {
+    let path = match input {
+        Input::Path(path) if !http::is_url(path) => Some(path),
+        Input::Aliased { path, .. } => Some(path),
+        _ => None,
+    };
+    let metadata = path.and_then(|path| match args.symlinks {
+        walk::Symlinks::Show => fs::symlink_metadata(path).ok(),
+        _ => fs::metadata(path).ok(),
+    });
+    let follow = args.symlinks != walk::Symlinks::Show;
+    let context = path
+        .filter(|_| args.output.context)
+        .and_then(|path| meta::security_context(path.as_ref(), follow));
+    let xattrs = path
+        .filter(|_| args.output.xattrs)
+        .map(|path| meta::xattrs(path.as_ref(), follow));
+    let (mut label, mut text) = read_text(input, args);
+    if let Some(path) = path.filter(|_| args.paths != paths::Style::Given) {
+        // keep anything read_text added after the path, like " (gzip)"
+        if let Some(rest) = label.strip_prefix(path.as_str()) {
+            label = paths::display(path, args.paths) + rest;
+        }
+    }
+    if let Some(n) = args.lines {
+        text.keep_head(n);
+    }
+    if let Some(n) = args.tail {
+        text.keep_tail(n);
+    }
+    output::Entry {
+        label,
+        text,
+        metadata,
+        context,
+        xattrs,
+    }
+}
+
+/// Read an input, returning its label and contents.
+fn read_text(input: &Input, args: &Args) -> (String, Text) {
+    let (label, data) = match input {
+        Input::Path(path) => {
+            let is_link = || fs::symlink_metadata(path).is_ok_and(|md| md.is_symlink());
+            let format = args
+                .decompress
+                .then(|| decompress::Format::detect_file(path))
+                .flatten();
+            let text = if http::is_url(path) {
+                http::fetch(path, &args.read_opts)
+            } else if args.symlinks == walk::Symlinks::Show && is_link() {
+                Text::read_link(path)
+            } else if let Some(format) = format {
+                let limit = args.read_opts.read_limit();
+                let text = match format.decompress_file(path, limit) {
+                    Ok(data) => Text::from_bytes(data, &args.read_opts),
+                    Err(err) => Text::Err(err),
+                };
+                return (format!("{path} ({format})"), text);
+            } else {
+                Text::read(path, &args.read_opts)
+            };
+            return (path.clone(), text);
+        }
+        Input::Loaded { label, data } => (label, data),
+        Input::Failed { label, err } => {
+            return (
+                label.clone(),
+                Text::Err(io::Error::new(err.kind(), err.to_string())),
+            )
+        }
+        Input::Aliased { path, aliases } => {
+            let (label, text) = read_text(&Input::Path(path.clone()), args);
+            return (format!("{label} (also {})", aliases.join(", ")), text);
+        }
+    };
+
+    match args
+        .decompress
+        .then(|| decompress::Format::detect(data))
+        .flatten()
+    {
+        Some(format) => {
+            let text = match format.decompress(data, args.read_opts.read_limit()) {
+                Ok(data) => Text::from_bytes(data, &args.read_opts),
+                Err(err) => Text::Err(err),
+            };
+            (format!("{label} ({format})"), text)
+        }
+        None => (
+            label.clone(),
+            Text::from_bytes(data.clone(), &args.read_opts),
+        ),
+    }
+}
+
+/// Read and format all the inputs. Returns the output and whether any file couldn't be read.
+fn dump(inputs: &[Input], args: &Args) -> (Vec<u8>, bool) {
+    let mut entries: Vec<output::Entry> =
+        inputs.iter().map(|input| read_input(input, args)).collect();
+    let had_err = entries.iter().any(|ent| ent.text.is_err());
+    entries.retain(|ent| is_shown(ent, args));
+    if let Some(prefix) = &args.strip_prefix {
+        let prefix = match prefix.as_str() {
+            "" => paths::common_dir(entries.iter().map(|ent| ent.label.as_str())),
+            prefix => prefix.to_owned(),
+        };
+        for ent in &mut entries {
+            ent.label = paths::strip_prefix(&ent.label, &prefix);
+        }
+    }
+    output::sort(&mut entries, args.order);
+    let mut out = Vec::new();
+    output::write(&mut out, &entries, &args.output);
+    if args.summary {
+        let mut summary = output::Summary::default();
+        entries.iter().for_each(|ent| summary.add(ent));
+        write_summary(&mut out, &summary, args);
+    }
+    (out, had_err)
+}
+
+/// Whether an entry should be in the output, given `--errors-only` and `--binary skip`.
+fn is_shown(ent: &output::Entry, args: &Args) -> bool {
+    let hide_errors = args
+        .output
+        .error_format
+        .as_ref()
+        .is_some_and(template::Template::is_empty);
+    if args.errors_only {
+        ent.text.is_err() && !hide_errors
+    } else if ent.text.is_err() {
+        !hide_errors
     } else {
-        m.remove_many("files").unwrap().collect()
+        !(args.read_opts.binary == text::Binary::Skip && matches!(ent.text, Text::Binary(_)))
     }
 }
 
-fn main() {
-    let files: Vec<(String, Text)> = parse_args()
-        .into_iter()
-        .map(|path| {
-            let text = Text::read(&path);
-            (path, text)
-        })
-        .collect();
+/// Read and format the inputs one at a time, writing each as soon as it's ready. Returns whether any
+/// file couldn't be read.
+fn stream(inputs: &[Input], args: &Args) -> bool {
+    let mut had_err = false;
+    let mut buf = Vec::new();
+    let mut summary = output::Summary::default();
+    for input in inputs {
+        let mut ent = read_input(input, args);
+        had_err |= ent.text.is_err();
+        if !is_shown(&ent, args) {
+            continue;
+        }
+        if let Some(prefix) = &args.strip_prefix {
+            ent.label = paths::strip_prefix(&ent.label, prefix);
+        }
+        summary.add(&ent);
+        buf.clear();
+        output::write_entry(&mut buf, &ent, &args.output);
+        write_stdout(&buf);
+    }
+    if args.summary {
+        buf.clear();
+        write_summary(&mut buf, &summary, args);
+        write_stdout(&buf);
+    }
+    had_err
+}
 
-    // print one-liners
+/// Write each input's output into its own file under `dir`, at the same path as the input. Returns
+/// whether any file couldn't be read or written.
+fn split(inputs: &[Input], args: &Args, dir: &Path) -> bool {
     let mut had_err = false;
-    let max_pathlen = files
-        .iter()
-        .map(|(path, _)| path.len())
-        .max()
-        .expect("empty files list");
+    let mut summary = output::Summary::default();
+    for input in inputs {
+        let ent = read_input(input, args);
+        had_err |= ent.text.is_err();
+        if !is_shown(&ent, args) {
+            continue;
+        }
+        summary.add(&ent);
+
+        // keep everything inside dir, whether the path is absolute or goes up with ".."
+        let rel: PathBuf = Path::new(&ent.label)
+            .components()
+            .filter(|c| matches!(c, std::path::Component::Normal(_)))
+            .collect();
+        if rel.as_os_str().is_empty() {
+            eprintln!("tabby: no path to write {} to", ent.label);
+            had_err = true;
+            continue;
+        }
+        let path = dir.join(rel);
+        let mut out = Vec::new();
+        output::write(&mut out, std::slice::from_ref(&ent), &args.output);
+        // the text format separates multiline files with a blank line, which isn't needed here
+        let out = out.strip_prefix(b"\n").unwrap_or(&out);
+        if let Err(err) = write_file(&path, out, false, true) {
+            eprintln!("tabby: error writing {}: {err}", path.display());
+            had_err = true;
+        }
+    }
+    if args.summary {
+        eprintln!("{summary}");
+    }
+    had_err
+}
+
+/// Add the `--summary` line to the text format's output. Other formats are meant for programs, so
+/// it goes to stderr instead.
+fn write_summary(out: &mut Vec<u8>, summary: &output::Summary, args: &Args) {
+    if args.output.format == output::Format::Text {
+        if !out.is_empty() {
+            out.push(b'\n');
+        }
+        writeln!(out, "{summary}").unwrap();
+    } else {
+        eprintln!("{summary}");
+    }
+}
+
+/// Write output to a file. Unless appending, it's written to a temporary file first and then
+/// renamed into place, so that the file is never left half-written.
+fn write_file(path: &Path, data: &[u8], append: bool, mkdir: bool) -> io::Result<()> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    if mkdir {
+        fs::create_dir_all(dir)?;
+    }
+    if append {
+        return File::options()
+            .append(true)
+            .create(true)
+            .open(path)?
+            .write_all(data);
+    }
 
-    for (path, text) in files.iter().filter(|(_, text)| !text.is_multiline()) {
-        println!("{path:>max_pathlen$}: {text}");
-        had_err |= text.is_err();
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file name"))?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(name);
+    tmp_name.push(format!(".tmp{}", std::process::id()));
+    let tmp = dir.join(tmp_name);
+    let result = fs::write(&tmp, data).and_then(|_| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
     }
+    result
+}
 
-    // print multi-liners
-    for (path, text) in files.iter().filter(|(_, text)| text.is_multiline()) {
-        print!("\n{path}:\n{text}");
+/// Write output to stdout. If stdout is closed early, like when piping to `head`, exit quietly.
+fn write_stdout(buf: &[u8]) {
+    let mut stdout = io::stdout().lock();
+    if let Err(err) = stdout.write_all(buf).and_then(|_| stdout.flush()) {
+        if err.kind() != io::ErrorKind::BrokenPipe {
+            eprintln!("tabby: error writing output: {err}");
+        }
+        exit(1);
     }
+}
+
+/// Re-dump the inputs whenever one changes, or they're found to have changed when polled, until
+/// killed.
+fn watch(inputs: &[Input], args: &Args, mut last: Vec<u8>) -> ! {
+    let clear = io::stdout().is_terminal();
+    let paths = inputs.iter().filter_map(|input| match input {
+        Input::Path(path) if !http::is_url(path) => Some(Path::new(path)),
+        Input::Aliased { path, .. } => Some(Path::new(path)),
+        _ => None,
+    });
+    let mut watcher = watch::Watcher::new(paths);
 
+    loop {
+        watcher.wait(args.interval);
+        let (out, _) = dump(inputs, args);
+        if out != last {
+            // like watch(1), redraw the whole screen on a terminal and otherwise add a separator
+            write_stdout(if clear { b"\x1b[H\x1b[2J" } else { b"\n" });
+            write_stdout(&out);
+            last = out;
+        }
+    }
+}
+
+fn main() {
+    let mut args = parse_args();
+    let inputs = std::mem::take(&mut args.files);
+
+    if let Some(dir) = &args.split_to {
+        exit(split(&inputs, &args, dir) as i32);
+    }
+    // entries can only be streamed if they don't need to be sorted
+    // the common prefix to strip isn't known until everything's been read
+    let streamable = args.order == output::Order::Cli
+        && args.output_file.is_none()
+        && args.strip_prefix.as_deref() != Some("");
+    if args.output.format.is_streaming() && streamable && !args.watch {
+        exit(stream(&inputs, &args) as i32);
+    }
+    let (out, had_err) = dump(&inputs, &args);
+    if let Some(path) = &args.output_file {
+        if let Err(err) = write_file(path, &out, args.append, args.mkdir) {
+            eprintln!("tabby: error writing {}: {err}", path.display());
+            exit(1);
+        }
+        exit(had_err as i32);
+    }
+    if args.watch {
+        if io::stdout().is_terminal() {
+            write_stdout(b"\x1b[H\x1b[2J");
+        }
+        write_stdout(&out);
+        watch(&inputs, &args, out);
+    }
+
+    if !pager::page(&out, args.paging) {
+        write_stdout(&out);
+    }
     if had_err {
         exit(1)
     }
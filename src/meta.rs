@@ -0,0 +1,227 @@
+//! Describing file metadata, for `--long`
+
+use std::fmt::Write as _;
+use std::fs::Metadata;
+use std::path::Path;
+
+/// Format a file's type and permissions like `ls -l`, as in `-rw-r--r--`.
+#[cfg(unix)]
+pub fn mode_string(md: &Metadata) -> String {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    let ft = md.file_type();
+    let kind = if ft.is_dir() {
+        'd'
+    } else if ft.is_symlink() {
+        'l'
+    } else if ft.is_fifo() {
+        'p'
+    } else if ft.is_socket() {
+        's'
+    } else if ft.is_char_device() {
+        'c'
+    } else if ft.is_block_device() {
+        'b'
+    } else {
+        '-'
+    };
+
+    let mode = md.permissions().mode();
+    let mut s = String::with_capacity(10);
+    s.push(kind);
+    for (shift, special, set, unset) in [
+        (6, 0o4000, 's', 'S'),
+        (3, 0o2000, 's', 'S'),
+        (0, 0o1000, 't', 'T'),
+    ] {
+        let bits = (mode >> shift) & 0o7;
+        s.push(if bits & 4 != 0 { 'r' } else { '-' });
+        s.push(if bits & 2 != 0 { 'w' } else { '-' });
+        s.push(match (bits & 1 != 0, mode & special != 0) {
+            (true, true) => set,
+            (false, true) => unset,
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    s
+}
+
+#[cfg(not(unix))]
+pub fn mode_string(md: &Metadata) -> String {
+    let kind = if md.is_dir() { 'd' } else { '-' };
+    let write = if md.permissions().readonly() {
+        '-'
+    } else {
+        'w'
+    };
+    format!("{kind}r{write}")
+}
+
+/// Format a file's permissions in octal, including the setuid, setgid, and sticky bits, as in
+/// `0644`.
+#[cfg(unix)]
+pub fn mode_octal(md: &Metadata) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    format!("{:04o}", md.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+pub fn mode_octal(_md: &Metadata) -> String {
+    String::new()
+}
+
+/// Get a file's owner and group like `root:root`, using IDs for unknown users and groups.
+#[cfg(unix)]
+pub fn owner(md: &Metadata) -> String {
+    use std::collections::HashMap;
+    use std::os::unix::fs::MetadataExt;
+    use std::sync::OnceLock;
+
+    static USERS: OnceLock<HashMap<u32, String>> = OnceLock::new();
+    static GROUPS: OnceLock<HashMap<u32, String>> = OnceLock::new();
+
+    let users = USERS.get_or_init(|| read_id_names("/etc/passwd"));
+    let groups = GROUPS.get_or_init(|| read_id_names("/etc/group"));
+    let name = |names: &HashMap<u32, String>, id: u32| {
+        names.get(&id).cloned().unwrap_or_else(|| id.to_string())
+    };
+    format!("{}:{}", name(users, md.uid()), name(groups, md.gid()))
+}
+
+#[cfg(not(unix))]
+pub fn owner(_md: &Metadata) -> String {
+    String::new()
+}
+
+/// Read the names and IDs from /etc/passwd or /etc/group, where lines are `name:x:id:...`. This
+/// misses users from other sources like LDAP, which just show up as IDs instead.
+#[cfg(unix)]
+fn read_id_names(path: &str) -> std::collections::HashMap<u32, String> {
+    let text = std::fs::read_to_string(path).unwrap_or_default();
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let id = fields.nth(1)?.parse().ok()?;
+            Some((id, name.to_owned()))
+        })
+        .collect()
+}
+
+/// Get a file's SELinux security context like `ls -Z`, as in `system_u:object_r:etc_t:s0`.
+/// Returns None if SELinux isn't available or the file has no label.
+pub fn security_context(path: &Path, follow: bool) -> Option<String> {
+    let mut value = xattr(path, "security.selinux", follow)?;
+    // the kernel includes the C string's terminator
+    if value.last() == Some(&0) {
+        value.pop();
+    }
+    String::from_utf8(value).ok()
+}
+
+/// List a file's extended attributes and their values, like `getfattr -d -m -`. Values which
+/// aren't printable text are shown in hex, as in `0x0100000200200000`.
+pub fn xattrs(path: &Path, follow: bool) -> Vec<(String, String)> {
+    let Some(names) = xattr_names(path, follow) else {
+        return Vec::new();
+    };
+    names
+        .split(|&b| b == 0)
+        .filter(|name| !name.is_empty())
+        .filter_map(|name| {
+            let name = String::from_utf8_lossy(name).into_owned();
+            let value = xattr(path, &name, follow)?;
+            Some((name, xattr_value(&value)))
+        })
+        .collect()
+}
+
+fn xattr_value(value: &[u8]) -> String {
+    let text = value.strip_suffix(b"\0").unwrap_or(value);
+    match std::str::from_utf8(text) {
+        Ok(text) if !text.chars().any(char::is_control) => text.to_owned(),
+        _ => value.iter().fold("0x".to_owned(), |mut hex, b| {
+            write!(hex, "{b:02x}").unwrap();
+            hex
+        }),
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod sys {
+    pub use std::os::raw::{c_char, c_void};
+
+    extern "C" {
+        pub fn getxattr(
+            path: *const c_char,
+            name: *const c_char,
+            value: *mut c_void,
+            size: usize,
+        ) -> isize;
+        pub fn lgetxattr(
+            path: *const c_char,
+            name: *const c_char,
+            value: *mut c_void,
+            size: usize,
+        ) -> isize;
+        pub fn listxattr(path: *const c_char, list: *mut c_char, size: usize) -> isize;
+        pub fn llistxattr(path: *const c_char, list: *mut c_char, size: usize) -> isize;
+    }
+
+    /// Call one of the xattr functions to get the size of the value, and then again to fill it in.
+    /// The value could grow in between, in which case the second call fails and it's treated as
+    /// missing.
+    pub fn read_sized(get: impl Fn(*mut c_void, usize) -> isize) -> Option<Vec<u8>> {
+        let size = get(std::ptr::null_mut(), 0);
+        let mut value = vec![0u8; usize::try_from(size).ok()?];
+        let len = get(value.as_mut_ptr().cast(), value.len());
+        value.truncate(usize::try_from(len).ok()?);
+        Some(value)
+    }
+}
+
+/// Read an extended attribute of a file, or of a symlink itself if `follow` is false.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn xattr(path: &Path, name: &str, follow: bool) -> Option<Vec<u8>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let name = CString::new(name).ok()?;
+    let get = if follow {
+        sys::getxattr
+    } else {
+        sys::lgetxattr
+    };
+    // SAFETY: both strings are NUL-terminated, and the buffer is either null with size 0, which
+    // only measures the value, or valid for writes of its size
+    sys::read_sized(|buf, size| unsafe { get(path.as_ptr(), name.as_ptr(), buf, size) })
+}
+
+/// Get the NUL-separated names of a file's extended attributes.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn xattr_names(path: &Path, follow: bool) -> Option<Vec<u8>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let list = if follow {
+        sys::listxattr
+    } else {
+        sys::llistxattr
+    };
+    // SAFETY: as in xattr
+    sys::read_sized(|buf, size| unsafe { list(path.as_ptr(), buf.cast(), size) })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn xattr(_path: &Path, _name: &str, _follow: bool) -> Option<Vec<u8>> {
+    None
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn xattr_names(_path: &Path, _follow: bool) -> Option<Vec<u8>> {
+    None
+}
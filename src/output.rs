@@ -0,0 +1,1793 @@
+//! Formatting the dumped files for output
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write;
+
+use crate::color::{Palette, Style};
+use crate::meta;
+use crate::regex::Regex;
+use crate::template::Template;
+use crate::text::Text;
+use crate::time;
+use crate::width::width;
+
+/// A file which has been read, and the label to show for it
+#[derive(Debug)]
+pub struct Entry {
+    pub label: String,
+    pub text: Text,
+    /// Metadata for local files
+    pub metadata: Option<fs::Metadata>,
+    /// The SELinux security context of local files, if `--context` was given
+    pub context: Option<String>,
+    /// The extended attributes of local files and their values, if `--xattrs` was given
+    pub xattrs: Option<Vec<(String, String)>>,
+}
+
+impl Entry {
+    /// The file's contents, or its placeholder like `[fifo]`, unless it couldn't be read.
+    fn content(&self) -> Option<String> {
+        (!self.text.is_err()).then(|| self.text.to_string())
+    }
+
+    fn error(&self) -> Option<String> {
+        match &self.text {
+            Text::Err(err) => Some(err.to_string()),
+            _ => None,
+        }
+    }
+
+    /// The OS error code, like 2 for ENOENT, if the file couldn't be read.
+    pub fn error_code(&self) -> Option<i32> {
+        match &self.text {
+            Text::Err(err) => err.raw_os_error(),
+            _ => None,
+        }
+    }
+}
+
+/// Output formats
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Aligned one-liners followed by multiline files, for humans
+    #[default]
+    Text,
+    /// A JSON array of objects
+    Json,
+    /// One JSON object per line
+    Jsonl,
+    /// A YAML mapping of paths to contents
+    Yaml,
+    /// A TOML table of paths to contents
+    Toml,
+    /// CSV rows with a header
+    Csv,
+    /// Tab-separated rows with a header, with tabs and newlines escaped
+    Tsv,
+    /// A table with a row per file, optionally with borders
+    Table { borders: bool },
+    /// A heading and fenced code block per file
+    Markdown,
+    /// A standalone HTML page
+    Html,
+    /// NUL-terminated paths and contents
+    Print0,
+    /// Only the contents, in order
+    Bare,
+    /// Paths split up into a tree of directories, like `tree`
+    Tree,
+    /// Each distinct content followed by the paths of the files holding it, from
+    /// `--group-by-value`
+    Grouped,
+    /// Shell variable assignments for one-line files
+    Env,
+    /// An XML document with an element per file
+    Xml,
+    /// An Emacs org-mode heading and example block per file
+    Org,
+    /// GNU recutils records, for querying with `recsel`
+    Rec,
+}
+
+impl Format {
+    /// Whether entries can be written one at a time as they're read, with [`write_entry`].
+    pub fn is_streaming(self) -> bool {
+        matches!(
+            self,
+            Format::Jsonl | Format::Print0 | Format::Bare | Format::Env
+        )
+    }
+}
+
+/// How paths of one-line files are lined up in the text format
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    /// Pad paths on the left so that the separators line up
+    #[default]
+    Right,
+    /// Pad paths on the right so that the separators line up
+    Left,
+    /// Put separators right after paths, and pad after them so that the values line up
+    Values,
+    /// Don't pad paths
+    None,
+}
+
+/// Counts of the files in the output, for `--summary`
+#[derive(Debug, Default)]
+pub struct Summary {
+    oneline: usize,
+    multiline: usize,
+    errors: usize,
+    bytes: u64,
+}
+
+impl Summary {
+    pub fn add(&mut self, ent: &Entry) {
+        match &ent.text {
+            Text::Err(_) => self.errors += 1,
+            text if text.is_multiline() => self.multiline += 1,
+            _ => self.oneline += 1,
+        }
+        self.bytes += match &ent.text {
+            Text::Oneline(s) | Text::Multiline(s) => s.len() as u64,
+            Text::Raw(data) => data.len() as u64,
+            _ => 0,
+        };
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let files = self.oneline + self.multiline + self.errors;
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        write!(
+            f,
+            "{files} file{}, {} one-line, {} multi-line, {} error{}, {} total",
+            plural(files),
+            self.oneline,
+            self.multiline,
+            self.errors,
+            plural(self.errors),
+            crate::size::format(self.bytes),
+        )
+    }
+}
+
+/// Order of the output, from `--order`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// The order files were given on the command line or found in
+    #[default]
+    Cli,
+    /// Sorted by path
+    Name,
+    /// Sorted by contents, then path
+    Value,
+    /// Sorted by contents with the largest first, then path, from `--sort value`
+    ValueDescending,
+}
+
+/// Sort entries for the output. Sorting is stable, so `Order::Cli` leaves them as they are.
+pub fn sort(entries: &mut [Entry], order: Order) {
+    match order {
+        Order::Cli => (),
+        Order::Name => entries.sort_by(|a, b| a.label.cmp(&b.label)),
+        Order::Value => {
+            entries.sort_by_cached_key(|ent| (ValueKey::new(&ent.text, false), ent.label.clone()))
+        }
+        Order::ValueDescending => {
+            entries.sort_by_cached_key(|ent| (ValueKey::new(&ent.text, true), ent.label.clone()))
+        }
+    }
+}
+
+/// Sort key for contents, so that numbers compare by value rather than as text, and `9` comes
+/// before `10`. Numbers come before everything else either way.
+#[derive(Debug)]
+struct ValueKey {
+    number: Option<f64>,
+    text: String,
+    descending: bool,
+}
+
+impl ValueKey {
+    fn new(text: &Text, descending: bool) -> Self {
+        let text = text.to_string();
+        let number = text.trim().parse::<f64>().ok().filter(|n| n.is_finite());
+        Self {
+            number,
+            text,
+            descending,
+        }
+    }
+}
+
+impl Ord for ValueKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let ord = match (self.number, other.number) {
+            (Some(a), Some(b)) => a.total_cmp(&b).then_with(|| self.text.cmp(&other.text)),
+            (Some(_), None) => return Ordering::Less,
+            (None, Some(_)) => return Ordering::Greater,
+            (None, None) => self.text.cmp(&other.text),
+        };
+        if self.descending {
+            ord.reverse()
+        } else {
+            ord
+        }
+    }
+}
+
+impl PartialOrd for ValueKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ValueKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for ValueKey {}
+
+/// How multiline files are introduced in the text format, from `--heading-style`
+#[derive(Debug, Default, Clone)]
+pub enum HeadingStyle {
+    /// `path:`
+    #[default]
+    Colon,
+    /// `==> path <==`, like `tail` and `head` with several files
+    Tail,
+    /// `# path`
+    Comment,
+    Template(Template),
+}
+
+/// How to quote contents in the text format, from `--quote`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quote {
+    /// POSIX shell single quotes
+    Shell,
+}
+
+/// Settings controlling how files are formatted
+#[derive(Debug)]
+pub struct Options {
+    pub format: Format,
+    /// Put between paths and contents in the text format
+    pub separator: String,
+    pub align: Align,
+    /// Write each file with this template instead of the text format
+    pub template: Option<Template>,
+    /// Colors for the text format
+    pub palette: Palette,
+    /// Syntax highlight multiline files in the text format
+    #[cfg_attr(not(feature = "highlight"), allow(dead_code))]
+    pub highlight: bool,
+    /// Show each file's permissions, owner, size, and modification time in the text and table
+    /// formats
+    pub long: bool,
+    /// Number the lines of multiline files in the text format
+    pub number: bool,
+    /// Put before each line of multiline files in the text format
+    pub indent: String,
+    /// Truncate one-line files in the text format to keep lines within this many columns
+    pub max_width: Option<usize>,
+    /// Wrap one-line files to `max_width` instead of truncating them
+    pub wrap: bool,
+    pub heading_style: HeadingStyle,
+    /// Show how many lines and bytes each file has in the text format
+    pub info: bool,
+    /// Quote contents in the text format, which puts multiline files with the one-liners too
+    pub quote: Option<Quote>,
+    /// Paint matches of this pattern within contents in the text format
+    pub highlight_regex: Option<Regex>,
+    /// Start each entry in the text format with a symbol showing whether it could be read
+    pub icons: bool,
+    /// Fill the space between paths and values with dots in the text format
+    pub leaders: bool,
+    /// How to show files which couldn't be read in the text-like formats, from `--error-format`,
+    /// instead of `[Error: ...]`
+    pub error_format: Option<Template>,
+    /// How to show modification times, from `--time-style`
+    pub time_style: time::Style,
+    /// What to show for empty files in the text-like formats, from `--empty-placeholder`
+    pub empty_placeholder: String,
+    /// Show only the first line of multiline files, along with the one-liners, in the text
+    /// format
+    pub preview: bool,
+    /// Pack one-liners into columns in the text format, from `--grid`
+    pub grid: bool,
+    /// Show each file's mode and owner in the text and table formats, from `--perms`
+    pub perms: bool,
+    /// Show each file's SELinux security context in the text format, from `--context`
+    pub context: bool,
+    /// List each file's extended attributes, from `--xattrs`
+    pub xattrs: bool,
+    /// Show the contents of identical multiline files once, under all of their headers, in the text
+    /// format
+    pub dedupe: bool,
+    /// Follow numbers in known units, like a battery's energy in µWh, with a readable form in the
+    /// text-like formats
+    pub human: bool,
+}
+
+/// Format all the entries.
+pub fn write(out: &mut Vec<u8>, entries: &[Entry], opts: &Options) {
+    match opts.format {
+        Format::Text => match &opts.template {
+            Some(template) => entries.iter().for_each(|ent| {
+                writeln!(out, "{}", template.render(ent, &opts.time_style)).unwrap()
+            }),
+            None => write_text(out, entries, opts),
+        },
+        Format::Json => write_json(out, entries),
+        Format::Jsonl | Format::Print0 | Format::Bare | Format::Env => {
+            entries.iter().for_each(|ent| write_entry(out, ent, opts))
+        }
+        Format::Yaml => write_yaml(out, entries),
+        Format::Toml => write_toml(out, entries),
+        Format::Csv => write_csv(out, entries),
+        Format::Tsv => write_tsv(out, entries),
+        Format::Markdown => write_markdown(out, entries),
+        Format::Tree => write_tree(out, entries, opts),
+        Format::Grouped => write_grouped(out, entries, opts),
+        Format::Html => write_html(out, entries),
+        Format::Xml => write_xml(out, entries),
+        Format::Org => write_org(out, entries),
+        Format::Rec => write_rec(out, entries),
+        Format::Table { borders } if opts.long || opts.perms => {
+            let rows = entries
+                .iter()
+                .map(|ent| {
+                    let mut row = if opts.long {
+                        long_fields(ent, opts).to_vec()
+                    } else {
+                        perms_fields(ent).to_vec()
+                    };
+                    row.extend([ent.label.clone(), value_text(ent, opts)]);
+                    row
+                })
+                .collect();
+            let headers: &[&str] = if opts.long {
+                &["MODE", "OWNER", "SIZE", "MTIME", "PATH", "VALUE"]
+            } else {
+                &["OCTAL", "MODE", "OWNER", "PATH", "VALUE"]
+            };
+            write_table(out, headers, rows, borders);
+        }
+        Format::Table { borders } => {
+            let rows = entries
+                .iter()
+                .map(|ent| vec![ent.label.clone(), value_text(ent, opts)])
+                .collect();
+            write_table(out, &["PATH", "VALUE"], rows, borders);
+        }
+    }
+}
+
+/// Format a single entry in a streaming format.
+pub fn write_entry(out: &mut Vec<u8>, ent: &Entry, opts: &Options) {
+    match opts.format {
+        Format::Jsonl => writeln!(out, "{}", json_object(ent)).unwrap(),
+        // errors are left as "[Error: ...]" placeholders, like in the text format
+        Format::Print0 => {
+            write!(out, "{}\0", ent.label).unwrap();
+            match &ent.text {
+                Text::Raw(data) => out.extend_from_slice(data),
+                text => write!(out, "{text}").unwrap(),
+            }
+            out.push(b'\0');
+        }
+        Format::Bare if ent.text.is_multiline() => match &ent.text {
+            Text::Raw(data) => out.extend_from_slice(data),
+            text => write!(out, "{text}").unwrap(),
+        },
+        Format::Bare => writeln!(out, "{}", ent.text).unwrap(),
+        // anything else can't be loaded with `eval $(tabby --env ...)`, which joins lines together
+        Format::Env => {
+            if let Text::Oneline(text) = &ent.text {
+                writeln!(out, "{}={}", env_name(&ent.label), shell_quote(text)).unwrap();
+            }
+        }
+        _ => unreachable!("{:?} isn't a streaming format", opts.format),
+    }
+}
+
+fn write_text(out: &mut Vec<u8>, entries: &[Entry], opts: &Options) {
+    let colors = &opts.palette;
+    let mut right = Vec::new();
+    let mut fields: Vec<Vec<String>> = entries
+        .iter()
+        .map(|ent| {
+            if opts.long {
+                long_fields(ent, opts).to_vec()
+            } else if opts.perms {
+                perms_fields(ent).to_vec()
+            } else {
+                Vec::new()
+            }
+        })
+        .collect();
+    if opts.long {
+        right.extend([false, false, true, false]);
+    } else if opts.perms {
+        right.extend([false, false, false]);
+    }
+    if opts.context {
+        for (row, ent) in fields.iter_mut().zip(entries) {
+            row.push(ent.context.clone().unwrap_or_else(|| "?".to_owned()));
+        }
+        right.push(false);
+    }
+    let mut prefixes = if right.is_empty() {
+        vec![String::new(); entries.len()]
+    } else {
+        column_prefixes(&fields, &right)
+    };
+    if opts.icons {
+        for (prefix, ent) in prefixes.iter_mut().zip(entries) {
+            let icon = match ent.text {
+                Text::Err(_) => '✗',
+                Text::Binary(_) | Text::Raw(_) => '⌁',
+                Text::Multiline(_) => '≡',
+                _ => '✓',
+            };
+            prefix.insert_str(0, &format!("{icon} "));
+        }
+    }
+    let lines = || entries.iter().zip(&prefixes);
+    // a quoted multiline file is a single shell word, even if it spans several lines
+    let is_oneline = |ent: &Entry| opts.quote.is_some() || opts.preview || !ent.text.is_multiline();
+
+    // print one-liners
+    if opts.grid {
+        write_grid(
+            out,
+            lines().filter(|(ent, _)| is_oneline(ent)).collect(),
+            opts,
+        );
+    }
+    let max_pathlen = entries
+        .iter()
+        .map(|ent| width(&ent.label))
+        .max()
+        .unwrap_or(0);
+    for (ent, prefix) in lines().filter(|(ent, _)| is_oneline(ent) && !opts.grid) {
+        // pad outside the escape sequences so that they don't count towards the width
+        let pad = max_pathlen - width(&ent.label);
+        // symlinks read as `path -> target`
+        let sep = if matches!(ent.text, Text::Link(_)) {
+            " "
+        } else {
+            &opts.separator
+        };
+        let (lpad, gap) = match opts.align {
+            // leaders replace the separator, so that the dots run right up to the value
+            _ if opts.leaders => (0, format!(" {} ", ".".repeat(pad + 3))),
+            Align::Right => (pad, sep.to_owned()),
+            Align::Left => (0, format!("{:pad$}{sep}", "")),
+            Align::Values => (0, format!("{sep}{:pad$}", "")),
+            Align::None => (0, sep.to_owned()),
+        };
+        let mut text = match (opts.quote, ent.content()) {
+            (Some(Quote::Shell), Some(content)) => shell_quote(&content),
+            _ => value_text(ent, opts),
+        };
+        let mut info = String::new();
+        if let Some((first, more)) = opts.preview.then(|| preview(&ent.text)).flatten() {
+            text = if opts.quote.is_some() {
+                shell_quote(first.as_str())
+            } else {
+                first
+            };
+            info = more;
+        }
+        if let Some(counts) = opts.info.then(|| counts(ent)).flatten() {
+            write!(info, " ({counts})").unwrap();
+        }
+        let indent = width(prefix) + lpad + width(&ent.label) + width(&gap);
+        // cutting off quoted contents would leave them unbalanced
+        let mut lines = match opts.max_width.filter(|_| opts.quote.is_none()) {
+            Some(max) if opts.wrap => wrap(&text, max.saturating_sub(indent)),
+            Some(max) => vec![truncate(&text, max.saturating_sub(indent + width(&info)))],
+            None => vec![Cow::Borrowed(text.as_str())],
+        };
+        if !info.is_empty() {
+            lines.last_mut().unwrap().to_mut().push_str(&info);
+        }
+        let (first, rest) = lines.split_first().unwrap();
+        if ent.text.is_err() {
+            let label = &ent.label;
+            let line = format!("{label}{gap}{first}");
+            writeln!(out, "{prefix}{:lpad$}{}", "", colors.error.paint(line)).unwrap();
+        } else {
+            let label = colors.path.paint(&ent.label);
+            let value = paint_value(first, ent, opts);
+            writeln!(out, "{prefix}{:lpad$}{label}{gap}{value}", "").unwrap();
+        }
+        // continuation lines start under the value
+        for line in rest {
+            let line = if ent.text.is_err() {
+                colors.error.paint(line).to_string()
+            } else {
+                paint_value(line, ent, opts)
+            };
+            writeln!(out, "{:indent$}{line}", "").unwrap();
+        }
+        write_xattrs(out, ent, indent, colors);
+    }
+
+    // print multi-liners, with the headers of files that have the same contents stacked together
+    // above a single copy of the body if deduplicating
+    let mut groups: Vec<Vec<(&Entry, &String)>> = Vec::new();
+    let mut index: HashMap<&[u8], usize> = HashMap::new();
+    for (ent, prefix) in lines().filter(|(ent, _)| !is_oneline(ent)) {
+        match opts.dedupe.then(|| contents(&ent.text)).flatten() {
+            Some(data) => match index.get(data) {
+                Some(&i) => groups[i].push((ent, prefix)),
+                None => {
+                    index.insert(data, groups.len());
+                    groups.push(vec![(ent, prefix)]);
+                }
+            },
+            None => groups.push(vec![(ent, prefix)]),
+        }
+    }
+    for group in groups {
+        out.push(b'\n');
+        for (ent, prefix) in &group {
+            let header = match &opts.heading_style {
+                HeadingStyle::Colon => format!("{}:", ent.label),
+                HeadingStyle::Tail => format!("==> {} <==", ent.label),
+                HeadingStyle::Comment => format!("# {}", ent.label),
+                HeadingStyle::Template(template) => template.render(ent, &opts.time_style),
+            };
+            let header = match opts.info.then(|| counts(ent)).flatten() {
+                Some(counts) => format!("{header} ({counts})"),
+                None => header,
+            };
+            writeln!(out, "{prefix}{}", colors.header.paint(header)).unwrap();
+            write_xattrs(out, ent, width(prefix) + 2, colors);
+        }
+        let ent = group[0].0;
+        if let Text::Raw(data) = &ent.text {
+            out.extend_from_slice(data);
+            if !data.ends_with(b"\n") {
+                out.push(b'\n');
+            }
+            continue;
+        }
+        let mut body = body(ent, opts);
+        if opts.number {
+            body = number_lines(&body);
+        }
+        if !opts.indent.is_empty() {
+            body = indent_lines(&body, &opts.indent);
+        }
+        out.extend_from_slice(body.as_bytes());
+    }
+}
+
+/// Get the first line of a multiline file for `--preview`, and a marker like ` (+3 lines)` for the
+/// rest of it.
+fn preview(text: &Text) -> Option<(String, String)> {
+    if !text.is_multiline() {
+        return None;
+    }
+    let text = text.to_string();
+    let mut lines = text.lines();
+    let first = lines.next().unwrap_or_default().to_owned();
+    let more = match lines.count() {
+        0 => String::new(),
+        1 => " (+1 line)".to_owned(),
+        n => format!(" (+{n} lines)"),
+    };
+    Some((first, more))
+}
+
+/// Pack one-liners into as many columns as fit across `--max-width`, or 80 columns without a
+/// limit, filling each column from top to bottom like `ls`.
+fn write_grid(out: &mut Vec<u8>, lines: Vec<(&Entry, &String)>, opts: &Options) {
+    const GAP: usize = 2;
+    let colors = &opts.palette;
+    let max_width = opts.max_width.unwrap_or(80);
+    let cells: Vec<(String, usize)> = lines
+        .iter()
+        .map(|(ent, prefix)| {
+            let text = match opts.preview.then(|| preview(&ent.text)).flatten() {
+                Some((first, more)) => first + &more,
+                None => value_text(ent, opts),
+            };
+            let plain = format!("{prefix}{}{}{text}", ent.label, opts.separator);
+            let cell = if ent.text.is_err() {
+                format!(
+                    "{prefix}{}",
+                    colors
+                        .error
+                        .paint(format!("{}{}{text}", ent.label, opts.separator))
+                )
+            } else {
+                let text = paint_value(&text, ent, opts);
+                format!(
+                    "{prefix}{}{}{text}",
+                    colors.path.paint(&ent.label),
+                    opts.separator
+                )
+            };
+            (cell, width(&plain))
+        })
+        .collect();
+    if cells.is_empty() {
+        return;
+    }
+
+    // use the fewest rows that fit, which is every cell in its own row if even that doesn't
+    let column_widths = |rows: usize| -> Vec<usize> {
+        cells
+            .chunks(rows)
+            .map(|col| col.iter().map(|(_, w)| *w).max().unwrap())
+            .collect()
+    };
+    let rows = (1..cells.len())
+        .find(|&rows| {
+            let widths = column_widths(rows);
+            widths.iter().sum::<usize>() + GAP * (widths.len() - 1) <= max_width
+        })
+        .unwrap_or(cells.len());
+    let widths = column_widths(rows);
+    for row in 0..rows {
+        let mut line = String::new();
+        for (col, w) in widths.iter().enumerate() {
+            let Some((cell, cell_width)) = cells.get(col * rows + row) else {
+                break;
+            };
+            if col > 0 {
+                line.push_str(&" ".repeat(GAP));
+            }
+            line.push_str(cell);
+            // don't pad the last cell on the line
+            if cells.get((col + 1) * rows + row).is_some() {
+                line.push_str(&" ".repeat(w - cell_width));
+            }
+        }
+        writeln!(out, "{line}").unwrap();
+    }
+}
+
+/// Get the text to show for a value, with `--empty-placeholder` standing in for empty files,
+/// errors written with `--error-format`, and numbers followed by their `--human` forms.
+fn value_text(ent: &Entry, opts: &Options) -> String {
+    match (&ent.text, &opts.error_format) {
+        (Text::Oneline(s), _) if s.is_empty() => opts.empty_placeholder.clone(),
+        (Text::Oneline(s), _) if opts.human => match crate::human::describe(&ent.label, s) {
+            Some(human) => format!("{s} ({human})"),
+            None => s.clone(),
+        },
+        (Text::Err(_), Some(template)) => template.render(ent, &opts.time_style),
+        (text, _) => text.to_string(),
+    }
+}
+
+/// Pick the style for a one-line value by what it seems to be.
+fn value_style<'a>(colors: &'a Palette, text: &Text) -> &'a Style {
+    let s = match text {
+        Text::Oneline(s) => s.trim(),
+        Text::Err(_) => return &colors.error,
+        Text::Special(_) | Text::Dir | Text::TooLarge(_) | Text::Binary(_) => {
+            return &colors.placeholder
+        }
+        _ => return &colors.value,
+    };
+    const BOOLEANS: &[&str] = &[
+        "0", "1", "y", "n", "yes", "no", "on", "off", "true", "false", "enabled", "disabled",
+    ];
+    if s.is_empty() {
+        &colors.empty
+    } else if BOOLEANS.iter().any(|b| s.eq_ignore_ascii_case(b)) {
+        &colors.boolean
+    } else if is_number(s) {
+        &colors.number
+    } else {
+        &colors.value
+    }
+}
+
+/// Whether a value is a decimal number like `-12`, `3.5`, or `1e6`, or a hex number like `0x1f`.
+fn is_number(s: &str) -> bool {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        return !hex.is_empty() && hex.bytes().all(|b| b.is_ascii_hexdigit());
+    }
+    // f64 also accepts words like "inf" and "NaN", which aren't what sysfs means by them
+    s.bytes().any(|b| b.is_ascii_digit())
+        && !s
+            .bytes()
+            .any(|b| b.is_ascii_alphabetic() && b != b'e' && b != b'E')
+        && s.parse::<f64>().is_ok()
+}
+
+/// List an entry's extended attributes under it in the text format, one `name=value` per line.
+fn write_xattrs(out: &mut Vec<u8>, ent: &Entry, indent: usize, colors: &Palette) {
+    for (name, value) in ent.xattrs.iter().flatten() {
+        writeln!(out, "{:indent$}{}={value}", "", colors.path.paint(name)).unwrap();
+    }
+}
+
+/// The exact contents of a multiline file, for `--dedupe-content`
+fn contents(text: &Text) -> Option<&[u8]> {
+    match text {
+        Text::Multiline(s) => Some(s.as_bytes()),
+        Text::Raw(data) => Some(data),
+        _ => None,
+    }
+}
+
+/// A path component in `--tree` output
+#[derive(Default)]
+struct TreeNode {
+    name: String,
+    /// The entry for this path itself
+    entry: Option<usize>,
+    /// In the order that they were first seen
+    children: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    fn insert(&mut self, components: &[&str], entry: usize) {
+        let Some((first, rest)) = components.split_first() else {
+            self.entry = Some(entry);
+            return;
+        };
+        let pos = match self.children.iter().position(|child| child.name == *first) {
+            Some(pos) => pos,
+            None => {
+                self.children.push(TreeNode {
+                    name: first.to_string(),
+                    ..Default::default()
+                });
+                self.children.len() - 1
+            }
+        };
+        self.children[pos].insert(rest, entry);
+    }
+
+    /// Merge chains of directories with only one child each, like `a/b/c`, into a single node so
+    /// that deep paths don't waste a level of indentation on every component.
+    fn collapse(&mut self) {
+        for child in &mut self.children {
+            while child.entry.is_none()
+                && child.children.len() == 1
+                && !child.children[0].children.is_empty()
+            {
+                let grandchild = child.children.pop().unwrap();
+                let sep = if child.name.ends_with('/') { "" } else { "/" };
+                child.name = format!("{}{sep}{}", child.name, grandchild.name);
+                child.children = grandchild.children;
+                child.entry = grandchild.entry;
+            }
+            child.collapse();
+        }
+    }
+}
+
+fn write_tree(out: &mut Vec<u8>, entries: &[Entry], opts: &Options) {
+    let mut root = TreeNode::default();
+    for (i, ent) in entries.iter().enumerate() {
+        let mut components: Vec<&str> = ent.label.split('/').filter(|c| !c.is_empty()).collect();
+        if ent.label.starts_with('/') {
+            components.insert(0, "/");
+        }
+        root.insert(&components, i);
+    }
+    root.collapse();
+    for node in &root.children {
+        write_tree_node(out, entries, opts, node, "", "");
+    }
+}
+
+/// Write a node of the tree and its children. `first` goes before the node's own line and `rest`
+/// before every line under it.
+fn write_tree_node(
+    out: &mut Vec<u8>,
+    entries: &[Entry],
+    opts: &Options,
+    node: &TreeNode,
+    first: &str,
+    rest: &str,
+) {
+    let colors = &opts.palette;
+    let name = colors.path.paint(&node.name);
+    match node.entry.map(|i| &entries[i]) {
+        Some(ent) if ent.text.is_err() => {
+            let line = format!("{}{}{}", node.name, opts.separator, value_text(ent, opts));
+            writeln!(out, "{first}{}", colors.error.paint(line)).unwrap();
+        }
+        Some(ent) if ent.text.is_multiline() => {
+            writeln!(out, "{first}{name}{}", opts.separator.trim_end()).unwrap();
+            // the body goes under the node, beside the line to any later siblings
+            let indent = if node.children.is_empty() {
+                format!("{rest}  ")
+            } else {
+                format!("{rest}│ ")
+            };
+            for line in ent.text.to_string().lines() {
+                let indent = if line.is_empty() {
+                    indent.trim_end()
+                } else {
+                    &indent
+                };
+                writeln!(out, "{indent}{line}").unwrap();
+            }
+        }
+        Some(ent) => {
+            let value = value_style(colors, &ent.text).paint(value_text(ent, opts));
+            writeln!(out, "{first}{name}{}{value}", opts.separator).unwrap();
+        }
+        None => writeln!(out, "{first}{name}").unwrap(),
+    }
+    for (i, child) in node.children.iter().enumerate() {
+        if i + 1 < node.children.len() {
+            write_tree_node(
+                out,
+                entries,
+                opts,
+                child,
+                &format!("{rest}├── "),
+                &format!("{rest}│   "),
+            );
+        } else {
+            write_tree_node(
+                out,
+                entries,
+                opts,
+                child,
+                &format!("{rest}└── "),
+                &format!("{rest}    "),
+            );
+        }
+    }
+}
+
+/// Write each distinct content once, in the order they were first seen, followed by the indented
+/// paths of the files holding it. Files which couldn't be read are grouped by their error.
+fn write_grouped(out: &mut Vec<u8>, entries: &[Entry], opts: &Options) {
+    let colors = &opts.palette;
+    let mut groups: Vec<(&Entry, Vec<&str>)> = Vec::new();
+    let mut index: HashMap<(bool, String), usize> = HashMap::new();
+    for ent in entries {
+        let key = (ent.text.is_err(), ent.text.to_string());
+        let i = *index.entry(key).or_insert_with(|| {
+            groups.push((ent, Vec::new()));
+            groups.len() - 1
+        });
+        groups[i].1.push(&ent.label);
+    }
+
+    // multiline contents need a blank line to tell where one group's paths end and the next begins
+    let spaced = groups.iter().any(|(ent, _)| ent.text.is_multiline());
+    for (i, (ent, labels)) in groups.iter().enumerate() {
+        if spaced && i > 0 {
+            out.push(b'\n');
+        }
+        let text = value_text(ent, opts);
+        if ent.text.is_err() {
+            writeln!(out, "{}", colors.error.paint(&text)).unwrap();
+        } else {
+            writeln!(out, "{}", colors.header.paint(text.trim_end_matches('\n'))).unwrap();
+        }
+        for label in labels {
+            writeln!(out, "    {}", colors.path.paint(label)).unwrap();
+        }
+    }
+}
+
+/// Get the `--long` columns for an entry: permissions, owner, size, and modification time. Entries
+/// without metadata, like URLs and files which couldn't be found, get a `?` in each column.
+fn long_fields(ent: &Entry, opts: &Options) -> [String; 4] {
+    let unknown = || "?".to_owned();
+    match &ent.metadata {
+        Some(md) => [
+            meta::mode_string(md),
+            meta::owner(md),
+            md.len().to_string(),
+            md.modified()
+                .map_or_else(|_| unknown(), |t| time::format_with(t, &opts.time_style)),
+        ],
+        None => [unknown(), unknown(), unknown(), unknown()],
+    }
+}
+
+/// Get the `--perms` columns for an entry: the mode in octal and like `ls -l`, and the owner.
+/// Entries without metadata get a `?` in each column.
+fn perms_fields(ent: &Entry) -> [String; 3] {
+    match &ent.metadata {
+        Some(md) => [meta::mode_octal(md), meta::mode_string(md), meta::owner(md)],
+        None => ["?".to_owned(), "?".to_owned(), "?".to_owned()],
+    }
+}
+
+/// Line up columns like those of `--long` for all the entries, so that the one-liners' paths stay
+/// aligned. Columns are right-aligned where `right` is set, and left-aligned otherwise.
+fn column_prefixes(rows: &[Vec<String>], right: &[bool]) -> Vec<String> {
+    let mut widths = vec![0; right.len()];
+    for row in rows {
+        for (w, field) in widths.iter_mut().zip(row) {
+            *w = (*w).max(width(field));
+        }
+    }
+    // format's own padding counts characters rather than columns
+    rows.iter()
+        .map(|row| {
+            let mut prefix = String::new();
+            for ((field, w), &right) in row.iter().zip(&widths).zip(right) {
+                let pad = " ".repeat(w - width(field));
+                if right {
+                    prefix.push_str(&pad);
+                    prefix.push_str(field);
+                } else {
+                    prefix.push_str(field);
+                    prefix.push_str(&pad);
+                }
+                prefix.push(' ');
+            }
+            prefix.push(' ');
+            prefix
+        })
+        .collect()
+}
+
+/// Count the lines and bytes in a file, like `3 lines, 42 B`, unless it has no contents.
+fn counts(ent: &Entry) -> Option<String> {
+    let (lines, bytes) = match &ent.text {
+        Text::Oneline(s) => (1, s.len()),
+        Text::Multiline(s) => (s.lines().count(), s.len()),
+        Text::Raw(data) => (data.split_inclusive(|&b| b == b'\n').count(), data.len()),
+        _ => return None,
+    };
+    let plural = if lines == 1 { "" } else { "s" };
+    Some(format!(
+        "{lines} line{plural}, {}",
+        crate::size::format(bytes as u64)
+    ))
+}
+
+/// Shorten text to at most `max` columns, marking where it was cut off with an ellipsis.
+fn truncate(text: &str, max: usize) -> Cow<'_, str> {
+    if width(text) <= max {
+        return Cow::Borrowed(text);
+    }
+    // leave a column for the ellipsis
+    let mut used = 0;
+    let mut end = 0;
+    for (i, c) in text.char_indices() {
+        used += crate::width::char_width(c);
+        if used + 1 > max {
+            break;
+        }
+        end = i + c.len_utf8();
+    }
+    Cow::Owned(format!("{}…", &text[..end]))
+}
+
+/// Split text into lines of at most `max` columns, breaking after spaces where possible.
+fn wrap(text: &str, max: usize) -> Vec<Cow<'_, str>> {
+    let mut lines = Vec::new();
+    let mut rest = text;
+    while width(rest) > max {
+        let mut used = 0;
+        let mut end = 0;
+        let mut after_space = None;
+        for (i, c) in rest.char_indices() {
+            used += crate::width::char_width(c);
+            if used > max {
+                break;
+            }
+            end = i + c.len_utf8();
+            if c == ' ' {
+                after_space = Some(end);
+            }
+        }
+        // always take at least one character, even if it's too wide, so that this finishes
+        let end = after_space
+            .unwrap_or(end)
+            .max(rest.chars().next().map_or(0, char::len_utf8));
+        lines.push(Cow::Borrowed(rest[..end].trim_end_matches(' ')));
+        rest = &rest[end..];
+    }
+    lines.push(Cow::Borrowed(rest));
+    lines
+}
+
+/// Paint a line of a one-line file's value in its style, with any `--highlight` matches painted
+/// over it.
+fn paint_value(line: &str, ent: &Entry, opts: &Options) -> String {
+    let style = value_style(&opts.palette, &ent.text);
+    match &opts.highlight_regex {
+        Some(regex) => paint_matches(line, regex, &opts.palette.matched, style),
+        None => style.paint(line).to_string(),
+    }
+}
+
+/// Paint each match of a pattern in a line, and the text between them in `base`. Styles can't be
+/// nested, since the reset at the end of a match would end the outer style too.
+fn paint_matches(line: &str, regex: &Regex, style: &Style, base: &Style) -> String {
+    let mut out = String::with_capacity(line.len());
+    // the end of what's been written, and where to look for the next match
+    let (mut pos, mut search) = (0, 0);
+    while let Some((start, end)) = regex.find_at(line, search) {
+        if start == end {
+            // an empty match has nothing to paint, but skip a character so that this finishes
+            let Some(c) = line[start..].chars().next() else {
+                break;
+            };
+            search = start + c.len_utf8();
+            continue;
+        }
+        if pos < start {
+            write!(out, "{}", base.paint(&line[pos..start])).unwrap();
+        }
+        write!(out, "{}", style.paint(&line[start..end])).unwrap();
+        (pos, search) = (end, end);
+    }
+    if pos < line.len() {
+        write!(out, "{}", base.paint(&line[pos..])).unwrap();
+    }
+    out
+}
+
+/// Get the body of a multiline file for the text format.
+#[cfg_attr(not(feature = "highlight"), allow(unused_variables))]
+fn body(ent: &Entry, opts: &Options) -> String {
+    if let (Some(regex), Text::Multiline(text)) = (&opts.highlight_regex, &ent.text) {
+        // match line by line so that ^ and $ match at the start and end of each one
+        let mut out = String::with_capacity(text.len());
+        for line in text.split_inclusive('\n') {
+            let (line, newline) = line.strip_suffix('\n').map_or((line, ""), |l| (l, "\n"));
+            out.push_str(&paint_matches(
+                line,
+                regex,
+                &opts.palette.matched,
+                &Style::default(),
+            ));
+            out.push_str(newline);
+        }
+        return out;
+    }
+    #[cfg(feature = "highlight")]
+    if let (true, Text::Multiline(text)) = (opts.highlight, &ent.text) {
+        if let Some(text) = crate::highlight::highlight(&ent.label, text) {
+            return text;
+        }
+    }
+    ent.text.to_string()
+}
+
+/// Number each line of a multiline file's body, the same way as `cat -n`.
+fn number_lines(body: &str) -> String {
+    let mut out = String::with_capacity(body.len() + body.len() / 4);
+    for (i, line) in body.split_inclusive('\n').enumerate() {
+        write!(out, "{:6}\t{line}", i + 1).unwrap();
+    }
+    out
+}
+
+/// Put a prefix before each line of a multiline file's body. Blank lines get the prefix without
+/// its trailing whitespace.
+fn indent_lines(body: &str, prefix: &str) -> String {
+    let mut out = String::with_capacity(body.len() + body.len() / 4);
+    for line in body.split_inclusive('\n') {
+        if line.trim_end_matches(['\n', '\r']).is_empty() {
+            out.push_str(prefix.trim_end());
+        } else {
+            out.push_str(prefix);
+        }
+        out.push_str(line);
+    }
+    out
+}
+
+fn write_json(out: &mut Vec<u8>, entries: &[Entry]) {
+    if entries.is_empty() {
+        out.extend_from_slice(b"[]\n");
+        return;
+    }
+    // one object per line keeps the output readable without a pretty-printer
+    out.extend_from_slice(b"[\n");
+    for (i, ent) in entries.iter().enumerate() {
+        let sep = if i + 1 < entries.len() { "," } else { "" };
+        writeln!(out, "  {}{sep}", json_object(ent)).unwrap();
+    }
+    out.extend_from_slice(b"]\n");
+}
+
+fn json_object(ent: &Entry) -> String {
+    let opt = |s: Option<String>| s.map_or_else(|| "null".to_owned(), |s| json_string(&s));
+    let mut obj = format!(
+        r#"{{"path": {}, "content": {}, "multiline": {}, "error": {}"#,
+        json_string(&ent.label),
+        opt(ent.content()),
+        ent.text.is_multiline(),
+        opt(ent.error()),
+    );
+    if let Some(xattrs) = &ent.xattrs {
+        let fields: Vec<String> = xattrs
+            .iter()
+            .map(|(name, value)| format!("{}: {}", json_string(name), json_string(value)))
+            .collect();
+        write!(obj, r#", "xattrs": {{{}}}"#, fields.join(", ")).unwrap();
+    }
+    obj.push('}');
+    obj
+}
+
+/// Quote and escape a JSON string.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn write_yaml(out: &mut Vec<u8>, entries: &[Entry]) {
+    if entries.is_empty() {
+        out.extend_from_slice(b"{}\n");
+        return;
+    }
+    for ent in entries {
+        let key = yaml_string(&ent.label);
+        match (&ent.text, ent.content()) {
+            (Text::Err(err), _) => writeln!(out, "{key}: null  # Error: {err}").unwrap(),
+            (Text::Multiline(text), _) if yaml_block_ok(text) => {
+                // keep every trailing newline with "|+", and say how far the block is indented
+                // when the first line itself starts with a space
+                let chomp = if text.ends_with("\n\n") { "+" } else { "" };
+                let indent = if text.starts_with(' ') { "2" } else { "" };
+                writeln!(out, "{key}: |{indent}{chomp}").unwrap();
+                for line in text.lines() {
+                    if line.is_empty() {
+                        out.push(b'\n');
+                    } else {
+                        writeln!(out, "  {line}").unwrap();
+                    }
+                }
+            }
+            (_, content) => writeln!(out, "{key}: {}", yaml_string(&content.unwrap())).unwrap(),
+        }
+    }
+}
+
+/// Whether text can be written as a YAML literal block scalar, which can't escape anything.
+fn yaml_block_ok(text: &str) -> bool {
+    !text
+        .chars()
+        .any(|c| c != '\n' && c != '\t' && c.is_control())
+        && !text.starts_with('\n')
+        && !text.contains('\r')
+}
+
+/// Format a string as a YAML scalar, plain if that's unambiguous and double-quoted otherwise.
+fn yaml_string(s: &str) -> String {
+    if yaml_plain_ok(s) {
+        return s.to_owned();
+    }
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Whether a string can be written as a plain YAML scalar and still be read back as the same
+/// string, rather than something like a number, boolean, or null.
+fn yaml_plain_ok(s: &str) -> bool {
+    const INDICATORS: &str = "-?:,[]{}#&*!|>'\"%@`";
+    let Some(first) = s.chars().next() else {
+        return false;
+    };
+    let lower = s.to_ascii_lowercase();
+    let looks_typed = matches!(
+        lower.as_str(),
+        "~" | "null" | "true" | "false" | "yes" | "no" | "on" | "off" | "y" | "n"
+    ) || lower.parse::<f64>().is_ok()
+        || lower.starts_with("0x")
+        || lower.starts_with("0o")
+        || lower.starts_with(".inf")
+        || lower.starts_with(".nan")
+        // YAML 1.1 also reads these as numbers
+        || (first.is_ascii_digit() && s.chars().all(|c| c.is_ascii_digit() || "_:.".contains(c)));
+
+    !looks_typed
+        && !INDICATORS.contains(first)
+        && !s.starts_with(' ')
+        && !s.ends_with(' ')
+        && !s.ends_with(':')
+        && !s.contains(": ")
+        && !s.contains(" #")
+        && !s.chars().any(|c| c.is_control())
+}
+
+fn write_toml(out: &mut Vec<u8>, entries: &[Entry]) {
+    for ent in entries {
+        let is_bare = !ent.label.is_empty()
+            && ent
+                .label
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-');
+        let key = if is_bare {
+            ent.label.clone()
+        } else {
+            toml_string(&ent.label)
+        };
+        match (&ent.text, ent.content()) {
+            // TOML has no null, so unreadable files are only noted in a comment
+            (Text::Err(err), _) => writeln!(out, "# {key}: Error: {err}").unwrap(),
+            (Text::Multiline(text), _) => {
+                writeln!(out, "{key} = \"\"\"").unwrap();
+                for c in text.chars() {
+                    match c {
+                        '"' | '\\' => write!(out, "\\{c}").unwrap(),
+                        '\n' | '\t' => write!(out, "{c}").unwrap(),
+                        c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+                        c => write!(out, "{c}").unwrap(),
+                    }
+                }
+                out.extend_from_slice(b"\"\"\"\n");
+            }
+            (_, content) => writeln!(out, "{key} = {}", toml_string(&content.unwrap())).unwrap(),
+        }
+    }
+}
+
+/// Quote and escape a TOML basic string, which is JSON's syntax except that DEL must be escaped.
+fn toml_string(s: &str) -> String {
+    json_string(s).replace('\x7f', "\\u007f")
+}
+
+fn write_csv(out: &mut Vec<u8>, entries: &[Entry]) {
+    out.extend_from_slice(b"path,content,is_multiline,error\n");
+    for ent in entries {
+        writeln!(
+            out,
+            "{},{},{},{}",
+            csv_field(&ent.label),
+            csv_field(&ent.content().unwrap_or_default()),
+            ent.text.is_multiline(),
+            csv_field(&ent.error().unwrap_or_default()),
+        )
+        .unwrap();
+    }
+}
+
+/// Quote a CSV field if needed, as described in RFC 4180.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) || s.starts_with(' ') || s.ends_with(' ') {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+fn write_tsv(out: &mut Vec<u8>, entries: &[Entry]) {
+    out.extend_from_slice(b"path\tcontent\tis_multiline\terror\n");
+    for ent in entries {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}",
+            tsv_field(&ent.label),
+            tsv_field(&ent.content().unwrap_or_default()),
+            ent.text.is_multiline(),
+            tsv_field(&ent.error().unwrap_or_default()),
+        )
+        .unwrap();
+    }
+}
+
+/// Escape backslashes, tabs, and newlines in a TSV field, the same way as PostgreSQL's and
+/// MySQL's text formats.
+fn tsv_field(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn write_markdown(out: &mut Vec<u8>, entries: &[Entry]) {
+    for (i, ent) in entries.iter().enumerate() {
+        if i > 0 {
+            out.push(b'\n');
+        }
+        writeln!(out, "### {}\n", markdown_escape(&ent.label)).unwrap();
+        let Some(content) = ent.content() else {
+            writeln!(out, "**Error:** {}", markdown_escape(&ent.error().unwrap())).unwrap();
+            continue;
+        };
+        // the fence has to be longer than any run of backticks in the contents
+        let mut longest = 0;
+        let mut run = 0;
+        for c in content.chars() {
+            run = if c == '`' { run + 1 } else { 0 };
+            longest = longest.max(run);
+        }
+        let fence = "`".repeat(longest.max(2) + 1);
+        writeln!(out, "{fence}{}", markdown_language(&ent.label)).unwrap();
+        out.extend_from_slice(content.as_bytes());
+        if !content.ends_with('\n') {
+            out.push(b'\n');
+        }
+        writeln!(out, "{fence}").unwrap();
+    }
+}
+
+fn write_org(out: &mut Vec<u8>, entries: &[Entry]) {
+    for ent in entries {
+        writeln!(out, "* {}", ent.label).unwrap();
+        let Some(content) = ent.content() else {
+            writeln!(out, "Error: {}", ent.error().unwrap()).unwrap();
+            continue;
+        };
+        out.extend_from_slice(b"#+BEGIN_EXAMPLE\n");
+        for line in content.lines() {
+            // lines which look like headings or keywords, even indented, are escaped with a comma
+            // after the indentation, which org removes when showing or exporting the block
+            let rest = line.trim_start_matches([' ', '\t']);
+            let indent = &line[..line.len() - rest.len()];
+            let unescaped = rest.trim_start_matches(',');
+            if unescaped.starts_with('*') || unescaped.starts_with("#+") {
+                writeln!(out, "{indent},{rest}").unwrap();
+            } else {
+                writeln!(out, "{line}").unwrap();
+            }
+        }
+        out.extend_from_slice(b"#+END_EXAMPLE\n");
+    }
+}
+
+/// Write a record per file with `Path` and either `Content` or `Error` fields, separated by blank
+/// lines.
+fn write_rec(out: &mut Vec<u8>, entries: &[Entry]) {
+    for (i, ent) in entries.iter().enumerate() {
+        if i > 0 {
+            out.push(b'\n');
+        }
+        write_rec_field(out, "Path", &ent.label);
+        match ent.content() {
+            Some(content) => write_rec_field(
+                out,
+                "Content",
+                content.strip_suffix('\n').unwrap_or(&content),
+            ),
+            None => write_rec_field(out, "Error", &ent.error().unwrap()),
+        }
+    }
+}
+
+/// Write a recutils field, continuing values with more than one line on lines starting with
+/// `+ `.
+fn write_rec_field(out: &mut Vec<u8>, name: &str, value: &str) {
+    let mut lines = value.split('\n');
+    let first = lines.next().unwrap();
+    // recutils ignores the blanks after the colon, so a leading one has to go on its own line
+    if first.starts_with([' ', '\t']) {
+        writeln!(out, "{name}:\n+ {first}").unwrap();
+    } else if first.is_empty() {
+        writeln!(out, "{name}:").unwrap();
+    } else {
+        writeln!(out, "{name}: {first}").unwrap();
+    }
+    for line in lines {
+        writeln!(out, "+ {line}").unwrap();
+    }
+}
+
+/// Backslash-escape the characters which could be taken as Markdown formatting.
+fn markdown_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\`*_[]<>#|~".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Guess the language hint for a fenced code block from a file's extension.
+fn markdown_language(name: &str) -> &'static str {
+    let base = name.rsplit('/').next().unwrap_or(name);
+    let ext = base.rsplit_once('.').map_or("", |(_, ext)| ext);
+    match ext.to_ascii_lowercase().as_str() {
+        "sh" | "bash" | "zsh" => "sh",
+        "py" => "python",
+        "rs" => "rust",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" => "cpp",
+        "go" => "go",
+        "js" | "mjs" => "javascript",
+        "ts" => "typescript",
+        "json" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "ini" | "cfg" | "conf" => "ini",
+        "xml" => "xml",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "md" => "markdown",
+        "diff" | "patch" => "diff",
+        "service" | "socket" | "timer" | "mount" => "systemd",
+        _ => "",
+    }
+}
+
+/// Turn a file's basename into a shell variable name, like `PRODUCT_NAME` for
+/// `/sys/class/dmi/id/product_name`.
+fn env_name(label: &str) -> String {
+    let base = label
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or(label);
+    let mut name: String = base
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        name.insert(0, '_');
+    }
+    name
+}
+
+/// Quote a string for POSIX shells with single quotes, which leave everything else literal.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+const HTML_HEAD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>tabby</title>
+<style>
+body { font-family: sans-serif; margin: 2em; }
+h2 { font-family: monospace; font-size: 1.1em; margin-top: 2em; }
+pre { background: #f4f4f4; padding: 0.5em 1em; overflow-x: auto; }
+.error { color: #b00; }
+</style>
+</head>
+<body>
+"#;
+
+fn write_html(out: &mut Vec<u8>, entries: &[Entry]) {
+    // every whitespace character ends an id, so those are the only ones which need replacing
+    let id = |ent: &Entry| ent.label.replace(char::is_whitespace, "_");
+
+    out.extend_from_slice(HTML_HEAD.as_bytes());
+    out.extend_from_slice(b"<ul>\n");
+    for ent in entries {
+        let href = html_escape(&url_escape(&id(ent)));
+        writeln!(
+            out,
+            "<li><a href=\"#{href}\">{}</a></li>",
+            html_escape(&ent.label)
+        )
+        .unwrap();
+    }
+    out.extend_from_slice(b"</ul>\n");
+
+    for ent in entries {
+        let label = html_escape(&ent.label);
+        writeln!(out, "<h2 id=\"{}\">{label}</h2>", html_escape(&id(ent))).unwrap();
+        match (ent.content(), ent.error()) {
+            (Some(content), _) => writeln!(out, "<pre>{}</pre>", html_escape(&content)).unwrap(),
+            (_, err) => {
+                let err = html_escape(&err.unwrap_or_default());
+                writeln!(out, "<p class=\"error\">Error: {err}</p>").unwrap();
+            }
+        }
+    }
+    out.extend_from_slice(b"</body>\n</html>\n");
+}
+
+/// Escape text for HTML element contents and quoted attributes.
+fn html_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Percent-encode everything but unreserved characters and slashes, for a URL fragment.
+fn url_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~/".contains(&b) {
+            out.push(b as char);
+        } else {
+            write!(out, "%{b:02X}").unwrap();
+        }
+    }
+    out
+}
+
+fn write_xml(out: &mut Vec<u8>, entries: &[Entry]) {
+    out.extend_from_slice(b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<files>\n");
+    for ent in entries {
+        let path = html_escape(&xml_chars(&ent.label));
+        match (ent.content(), ent.error()) {
+            (Some(content), _) => {
+                // CDATA can't contain "]]>", so split it between two sections
+                let content = xml_chars(&content).replace("]]>", "]]]]><![CDATA[>");
+                let multiline = ent.text.is_multiline();
+                let attrs = format!("path=\"{path}\" multiline=\"{multiline}\"");
+                writeln!(out, "  <file {attrs}><![CDATA[{content}]]></file>").unwrap();
+            }
+            (_, err) => {
+                let err = html_escape(&xml_chars(&err.unwrap_or_default()));
+                writeln!(out, "  <file path=\"{path}\" error=\"{err}\"/>").unwrap();
+            }
+        }
+    }
+    out.extend_from_slice(b"</files>\n");
+}
+
+/// Replace the control characters and noncharacters which XML 1.0 doesn't allow anywhere, even
+/// escaped.
+fn xml_chars(s: &str) -> Cow<'_, str> {
+    let invalid = |c: char| {
+        (c < ' ' && !matches!(c, '\t' | '\n' | '\r')) || matches!(c, '\u{fffe}' | '\u{ffff}')
+    };
+    if s.contains(invalid) {
+        Cow::Owned(
+            s.chars()
+                .map(|c| if invalid(c) { '\u{fffd}' } else { c })
+                .collect(),
+        )
+    } else {
+        Cow::Borrowed(s)
+    }
+}
+
+/// Write a table with a header row. Cells may contain several lines, which take up several rows.
+fn write_table(out: &mut Vec<u8>, header: &[&str], rows: Vec<Vec<String>>, borders: bool) {
+    let mut widths: Vec<usize> = header.iter().map(|h| width(h)).collect();
+    for row in &rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = cell.lines().map(width).fold(*w, usize::max);
+        }
+    }
+
+    let rule = |left: &str, mid: &str, right: &str| {
+        let cols: Vec<String> = widths.iter().map(|w| "─".repeat(w + 2)).collect();
+        format!("{left}{}{right}\n", cols.join(mid))
+    };
+    let write_row = |out: &mut Vec<u8>, cells: &[&str]| {
+        let mut line = String::new();
+        for (i, (cell, w)) in cells.iter().zip(&widths).enumerate() {
+            let pad = w - width(cell);
+            if borders {
+                write!(line, "│ {cell}{:pad$} ", "").unwrap();
+            } else if i + 1 < cells.len() {
+                write!(line, "{cell}{:pad$}  ", "").unwrap();
+            } else {
+                // no trailing whitespace after the last column
+                line.push_str(cell);
+            }
+        }
+        if borders {
+            line.push('│');
+        }
+        line.push('\n');
+        out.extend_from_slice(line.as_bytes());
+    };
+
+    if borders {
+        out.extend_from_slice(rule("┌", "┬", "┐").as_bytes());
+    }
+    write_row(out, header);
+    if borders {
+        out.extend_from_slice(rule("├", "┼", "┤").as_bytes());
+    }
+    for row in &rows {
+        let lines: Vec<Vec<&str>> = row.iter().map(|cell| cell.lines().collect()).collect();
+        let height = lines.iter().map(Vec::len).max().unwrap_or(0).max(1);
+        for i in 0..height {
+            let cells: Vec<&str> = lines
+                .iter()
+                .map(|l| l.get(i).copied().unwrap_or(""))
+                .collect();
+            write_row(out, &cells);
+        }
+    }
+    if borders {
+        out.extend_from_slice(rule("└", "┴", "┘").as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(label: &str, text: Text) -> Entry {
+        Entry {
+            label: label.to_owned(),
+            text,
+            metadata: None,
+            context: None,
+            xattrs: None,
+        }
+    }
+
+    fn written(write: fn(&mut Vec<u8>, &[Entry]), entries: &[Entry]) -> String {
+        let mut out = Vec::new();
+        write(&mut out, entries);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn org_escapes() {
+        let text = "* heading\n  #+END_EXAMPLE\n\t* item\n,#+begin\n # comment\nplain *\n";
+        assert_eq!(
+            written(write_org, &[entry("a", Text::Multiline(text.to_owned()))]),
+            "* a\n#+BEGIN_EXAMPLE\n,* heading\n  ,#+END_EXAMPLE\n\t,* item\n,,#+begin\n \
+             # comment\nplain *\n#+END_EXAMPLE\n"
+        );
+    }
+
+    #[test]
+    fn xml_cdata() {
+        let entries = [
+            entry("a", Text::Oneline("x]]>y".to_owned())),
+            entry("b", Text::Multiline("]]>\n]]\n".to_owned())),
+        ];
+        assert_eq!(
+            written(write_xml, &entries),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<files>\n\
+             \x20 <file path=\"a\" multiline=\"false\"><![CDATA[x]]]]><![CDATA[>y]]></file>\n\
+             \x20 <file path=\"b\" multiline=\"true\"><![CDATA[]]]]><![CDATA[>\n]]\n]]></file>\n\
+             </files>\n"
+        );
+    }
+
+    #[test]
+    fn xml_forbidden_chars() {
+        assert_eq!(xml_chars("tab\tcr\rlf\n"), "tab\tcr\rlf\n");
+        assert_eq!(xml_chars("nul\0esc\x1b"), "nul\u{fffd}esc\u{fffd}");
+        assert_eq!(
+            xml_chars("\u{fffe}\u{ffff}\u{fffd}é"),
+            "\u{fffd}\u{fffd}\u{fffd}é"
+        );
+        assert!(matches!(xml_chars("fine"), Cow::Borrowed(_)));
+
+        let entries = [entry("a\x01<\"&", Text::Oneline("b\x02".to_owned()))];
+        assert!(written(write_xml, &entries).contains(
+            "<file path=\"a\u{fffd}&lt;&quot;&amp;\" multiline=\"false\"><![CDATA[b\u{fffd}]]>"
+        ));
+    }
+
+    #[test]
+    fn yaml_plain_scalars() {
+        for plain in [
+            "hello",
+            "two words",
+            "a:b",
+            "path/to/file",
+            "x#y",
+            "v1.2.3",
+            "café",
+        ] {
+            assert!(yaml_plain_ok(plain), "{plain:?} should be plain");
+        }
+        let typed = [
+            "", "~", "null", "NULL", "yes", "No", "on", "OFF", "y", "true", "False", "0", "-1",
+            "+1", "1.5", "1e3", ".5", "0x1f", "0o17", "1_000", "12:30:00", ".inf", ".NaN",
+        ];
+        for s in typed {
+            assert!(!yaml_plain_ok(s), "{s:?} should be quoted");
+        }
+        let syntax = [
+            "- x", "-", "? x", "a: b", "a:", "#x", "a #b", " lead", "trail ", "[x]", "{x}", "*x",
+            "&x", "!x", "|x", ">x", "'x", "\"x", "%x", "@x", "`x", "tab\tx", "line\nx",
+        ];
+        for s in syntax {
+            assert!(!yaml_plain_ok(s), "{s:?} should be quoted");
+        }
+    }
+
+    #[test]
+    fn yaml_strings() {
+        assert_eq!(yaml_string("hello"), "hello");
+        assert_eq!(yaml_string("yes"), "\"yes\"");
+        assert_eq!(yaml_string(""), "\"\"");
+        assert_eq!(yaml_string("a: b"), "\"a: b\"");
+        assert_eq!(yaml_string("\"q\" \\"), r#""\"q\" \\""#);
+        assert_eq!(yaml_string("a\nb\tc\x01"), r#""a\nb\tc\u0001""#);
+    }
+
+    #[test]
+    fn env_names() {
+        assert_eq!(env_name("/sys/class/dmi/id/product_name"), "PRODUCT_NAME");
+        assert_eq!(env_name("power/runtime-status"), "RUNTIME_STATUS");
+        assert_eq!(env_name("cpu/0"), "_0");
+        assert_eq!(env_name("9p.conf"), "_9P_CONF");
+        assert_eq!(env_name("dir/"), "DIR");
+        assert_eq!(env_name("-x"), "_X");
+        assert_eq!(env_name("naïve"), "NA_VE");
+    }
+
+    #[test]
+    fn shell_quotes() {
+        assert_eq!(shell_quote("plain"), "'plain'");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote("$HOME `id` $(id)"), "'$HOME `id` $(id)'");
+        assert_eq!(shell_quote("a\nb"), "'a\nb'");
+        assert_eq!(shell_quote("back\\slash"), "'back\\slash'");
+    }
+
+    #[test]
+    fn csv_fields() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("a\nb"), "\"a\nb\"");
+        assert_eq!(csv_field("a\r\nb"), "\"a\r\nb\"");
+        assert_eq!(csv_field(" padded "), "\" padded \"");
+        assert_eq!(csv_field("tab\tback\\"), "tab\tback\\");
+    }
+
+    #[test]
+    fn tsv_fields() {
+        assert_eq!(tsv_field("plain, \"quoted\""), "plain, \"quoted\"");
+        assert_eq!(tsv_field("a\tb"), r"a\tb");
+        assert_eq!(tsv_field("a\r\nb\n"), r"a\r\nb\n");
+        assert_eq!(tsv_field(r"C:\dir\n"), r"C:\\dir\\n");
+    }
+}
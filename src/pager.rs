@@ -0,0 +1,64 @@
+//! Showing long output in a pager
+
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use crate::term;
+
+const DEFAULT_PAGER: &str = "less -FRX";
+
+/// When to use a pager, from `--paging`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Paging {
+    /// Only when writing to a terminal, and the output is taller than it
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Show the output in `$PAGER` if needed. Returns false if it wasn't paged, so that it still needs
+/// to be written to stdout, including when the pager couldn't be started.
+pub fn page(out: &[u8], paging: Paging) -> bool {
+    let wanted = match paging {
+        Paging::Auto => {
+            let lines = out.iter().filter(|&&b| b == b'\n').count();
+            term::size().is_some_and(|(_, rows)| lines >= rows)
+        }
+        Paging::Always => true,
+        Paging::Never => false,
+    };
+    if !wanted {
+        return false;
+    }
+
+    let pager = match std::env::var("PAGER") {
+        Ok(pager) if !pager.trim().is_empty() => pager,
+        _ => DEFAULT_PAGER.to_owned(),
+    };
+    // like git, let the shell split up the command so that quoting works
+    let mut cmd = if cfg!(unix) {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(&pager);
+        cmd
+    } else {
+        let mut words = pager.split_whitespace();
+        let mut cmd = Command::new(words.next().unwrap());
+        cmd.args(words);
+        cmd
+    };
+    let Ok(mut child) = cmd.stdin(Stdio::piped()).spawn() else {
+        return false;
+    };
+
+    let mut stdin = child.stdin.take().unwrap();
+    if let Err(err) = stdin.write_all(out) {
+        // the pager exiting before reading everything is fine, that's just the user quitting
+        if err.kind() != io::ErrorKind::BrokenPipe {
+            eprintln!("tabby: error writing to pager: {err}");
+        }
+    }
+    drop(stdin);
+    let _ = child.wait();
+    true
+}
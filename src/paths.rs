@@ -0,0 +1,97 @@
+//! Rewriting how paths are shown, for `--paths` and `--strip-prefix`
+
+use std::path::{Component, Path, PathBuf};
+
+/// How to show local paths, from `--paths`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// However they were given or found
+    #[default]
+    Given,
+    /// Relative to the current directory
+    Relative,
+    Absolute,
+    /// Only the filename
+    Basename,
+}
+
+/// Show a local path in the given style. Paths which can't be rewritten, like when the current
+/// directory is unknown, are shown as they are.
+pub fn display(path: &str, style: Style) -> String {
+    let rewritten = match style {
+        Style::Given => None,
+        Style::Relative => cwd().map(|cwd| relative_to(&absolute(path, &cwd), &cwd)),
+        Style::Absolute => cwd().map(|cwd| absolute(path, &cwd)),
+        Style::Basename => Path::new(path).file_name().map(PathBuf::from),
+    };
+    match rewritten {
+        Some(p) if !p.as_os_str().is_empty() => p.to_str().unwrap_or(path).to_owned(),
+        Some(_) => ".".to_owned(),
+        None => path.to_owned(),
+    }
+}
+
+fn cwd() -> Option<PathBuf> {
+    std::env::current_dir().ok()
+}
+
+/// Make a path absolute and remove `.` and `..` components. This doesn't resolve symlinks, so that
+/// paths like /sys/class/net/eth0 keep their familiar names.
+fn absolute(path: &str, cwd: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in cwd.join(path).components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                out.pop();
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Get the relative path from `base` to `path`, which are both absolute and normalized.
+fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    let mut path_parts = path.components().peekable();
+    let mut base_parts = base.components().peekable();
+    while let (Some(a), Some(b)) = (path_parts.peek(), base_parts.peek()) {
+        if a != b {
+            break;
+        }
+        path_parts.next();
+        base_parts.next();
+    }
+    let mut out: PathBuf = base_parts.map(|_| Component::ParentDir).collect();
+    out.extend(path_parts);
+    out
+}
+
+/// Find the directory which all the labels are under, including its trailing slash, like
+/// `/sys/class/net/` for `/sys/class/net/eth0/mtu` and `/sys/class/net/lo/mtu`.
+pub fn common_dir<'a>(labels: impl IntoIterator<Item = &'a str>) -> String {
+    let mut labels = labels.into_iter();
+    let Some(first) = labels.next() else {
+        return String::new();
+    };
+    // a file's own name is never part of the prefix
+    let mut prefix = first.rfind('/').map_or("", |i| &first[..=i]);
+    for label in labels {
+        while !label.starts_with(prefix) {
+            prefix = prefix[..prefix.len() - 1]
+                .rfind('/')
+                .map_or("", |i| &prefix[..=i]);
+        }
+    }
+    prefix.to_owned()
+}
+
+/// Remove a prefix from a label, along with the slash after it if the prefix doesn't end with one.
+/// Labels which don't start with the prefix, or are nothing but the prefix, are left alone.
+pub fn strip_prefix(label: &str, prefix: &str) -> String {
+    match label.strip_prefix(prefix) {
+        Some(rest) if prefix.ends_with('/') && !rest.is_empty() => rest.to_owned(),
+        Some(rest) if rest.len() > 1 && rest.starts_with('/') => rest[1..].to_owned(),
+        _ => label.to_owned(),
+    }
+}
@@ -0,0 +1,538 @@
+//! A small regular expression engine
+//!
+//! This supports the commonly used subset of Perl-style syntax: literals, `.`, bracket classes like
+//! `[a-z_]` and `[^0-9]`, the `\d`, `\w`, and `\s` classes and their negations, the anchors `^` and
+//! `$`, groups with `(...)` or `(?:...)`, alternation with `|`, and the quantifiers `*`, `+`, `?`,
+//! `{n}`, `{n,}`, and `{n,m}`, which are lazy when followed by `?`. Matching is unanchored unless
+//! the pattern uses `^` or `$`.
+//!
+//! Patterns are compiled to a small program which is run over the text in a single pass, so matching
+//! takes time proportional to the length of the text and never recurses, even on long lines of
+//! file contents.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Debug, Clone)]
+enum Node {
+    Empty,
+    Char(char),
+    /// Any character except a newline
+    Any,
+    Class(Class),
+    Start,
+    End,
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+        greedy: bool,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Class {
+    ranges: Vec<(char, char)>,
+    negated: bool,
+}
+
+impl Class {
+    fn matches(&self, c: char) -> bool {
+        self.ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != self.negated
+    }
+}
+
+const DIGIT: &[(char, char)] = &[('0', '9')];
+const WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+const SPACE: &[(char, char)] = &[('\t', '\r'), (' ', ' ')];
+
+/// Patterns which compile to more instructions than this are rejected, since counted repetitions
+/// like `(a{100}){100}` are expanded into copies
+const MAX_PROGRAM: usize = 100_000;
+
+/// One instruction of a compiled pattern. Matching runs all the ways through the program at once,
+/// a "Pike VM", so it takes time proportional to the length of the text rather than backtracking.
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    /// Continue at both targets, preferring the first
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+/// A compiled regular expression
+#[derive(Debug, Clone)]
+pub struct Regex {
+    prog: Vec<Inst>,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        let mut parser = Parser {
+            chars: pattern.chars().peekable(),
+        };
+        let root = parser.parse_alt()?;
+        if parser.chars.next().is_some() {
+            return Err("unmatched ')'".to_owned());
+        }
+        let mut prog = Vec::new();
+        compile(&root, &mut prog)?;
+        prog.push(Inst::Match);
+        Ok(Regex { prog })
+    }
+
+    /// Whether the pattern matches anywhere in `text`.
+    pub fn is_match(&self, text: &str) -> bool {
+        self.find_at(text, 0).is_some()
+    }
+
+    /// Find the leftmost match starting at or after byte offset `start`, returning its byte range.
+    /// Like Perl, the match is the one which the pattern's greedy and lazy quantifiers prefer,
+    /// rather than the longest.
+    pub fn find_at(&self, text: &str, start: usize) -> Option<(usize, usize)> {
+        // threads are kept in order of preference, each with the offset its match started at
+        let mut threads = Threads::new(self.prog.len());
+        let mut next = Threads::new(self.prog.len());
+        let mut found = None;
+        let mut pos = start;
+        loop {
+            if found.is_none() {
+                // a match starting here is less preferred than any which started earlier
+                threads.add(&self.prog, text, 0, pos, pos);
+            }
+            if threads.list.is_empty() {
+                break;
+            }
+            let c = text[pos..].chars().next();
+            let next_pos = pos + c.map_or(0, char::len_utf8);
+            next.clear();
+            for &(pc, from) in &threads.list {
+                let step = match &self.prog[pc] {
+                    Inst::Match => {
+                        // everything after this thread is less preferred, so it can stop now
+                        found = Some((from, pos));
+                        break;
+                    }
+                    Inst::Char(want) => c == Some(*want),
+                    Inst::Any => c.is_some_and(|c| c != '\n'),
+                    Inst::Class(class) => c.is_some_and(|c| class.matches(c)),
+                    _ => unreachable!("only consuming instructions are queued"),
+                };
+                if step {
+                    next.add(&self.prog, text, pc + 1, from, next_pos);
+                }
+            }
+            if c.is_none() {
+                break;
+            }
+            std::mem::swap(&mut threads, &mut next);
+            pos = next_pos;
+        }
+        found
+    }
+}
+
+/// The threads at one position in the text, with no instruction queued twice
+struct Threads {
+    list: Vec<(usize, usize)>,
+    queued: Vec<bool>,
+    stack: Vec<usize>,
+}
+
+impl Threads {
+    fn new(len: usize) -> Threads {
+        Threads {
+            list: Vec::new(),
+            queued: vec![false; len],
+            stack: Vec::new(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+        self.queued.fill(false);
+    }
+
+    /// Queue the instructions reachable from `pc` without consuming anything, in order of
+    /// preference, for a match which started at `from`
+    fn add(&mut self, prog: &[Inst], text: &str, pc: usize, from: usize, pos: usize) {
+        self.stack.push(pc);
+        while let Some(pc) = self.stack.pop() {
+            if std::mem::replace(&mut self.queued[pc], true) {
+                continue;
+            }
+            match prog[pc] {
+                Inst::Start if pos == 0 => self.stack.push(pc + 1),
+                Inst::End if pos == text.len() => self.stack.push(pc + 1),
+                Inst::Start | Inst::End => (),
+                Inst::Jump(to) => self.stack.push(to),
+                Inst::Split(first, second) => {
+                    self.stack.push(second);
+                    self.stack.push(first);
+                }
+                _ => self.list.push((pc, from)),
+            }
+        }
+    }
+}
+
+/// Append the instructions for `node` to `prog`.
+fn compile(node: &Node, prog: &mut Vec<Inst>) -> Result<(), String> {
+    if prog.len() > MAX_PROGRAM {
+        return Err("pattern is too large".to_owned());
+    }
+    match node {
+        Node::Empty => (),
+        Node::Char(c) => prog.push(Inst::Char(*c)),
+        Node::Any => prog.push(Inst::Any),
+        Node::Class(class) => prog.push(Inst::Class(class.clone())),
+        Node::Start => prog.push(Inst::Start),
+        Node::End => prog.push(Inst::End),
+        Node::Concat(nodes) => {
+            for node in nodes {
+                compile(node, prog)?;
+            }
+        }
+        Node::Alt(alts) => {
+            let mut jumps = Vec::new();
+            for (i, alt) in alts.iter().enumerate() {
+                if i + 1 == alts.len() {
+                    compile(alt, prog)?;
+                } else {
+                    let split = prog.len();
+                    prog.push(Inst::Split(0, 0));
+                    compile(alt, prog)?;
+                    jumps.push(prog.len());
+                    prog.push(Inst::Jump(0));
+                    prog[split] = Inst::Split(split + 1, prog.len());
+                }
+            }
+            for jump in jumps {
+                prog[jump] = Inst::Jump(prog.len());
+            }
+        }
+        Node::Repeat {
+            node,
+            min,
+            max,
+            greedy,
+        } => {
+            // the body is preferred when greedy, and what comes after it when lazy
+            let split = |prefer: usize, other: usize| {
+                if *greedy {
+                    Inst::Split(prefer, other)
+                } else {
+                    Inst::Split(other, prefer)
+                }
+            };
+            for _ in 0..*min {
+                compile(node, prog)?;
+            }
+            match max {
+                None => {
+                    // loop: split to the body or past it, and jump back after the body
+                    let top = prog.len();
+                    prog.push(Inst::Split(0, 0));
+                    compile(node, prog)?;
+                    prog.push(Inst::Jump(top));
+                    prog[top] = split(top + 1, prog.len());
+                }
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in *min..*max {
+                        splits.push(prog.len());
+                        prog.push(Inst::Split(0, 0));
+                        compile(node, prog)?;
+                    }
+                    for at in splits {
+                        prog[at] = split(at + 1, prog.len());
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn parse_alt(&mut self) -> Result<Node, String> {
+        let mut alts = vec![self.parse_concat()?];
+        while self.chars.next_if_eq(&'|').is_some() {
+            alts.push(self.parse_concat()?);
+        }
+        Ok(if alts.len() == 1 {
+            alts.pop().unwrap()
+        } else {
+            Node::Alt(alts)
+        })
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(&c) = self.chars.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            self.chars.next();
+            let atom = match c {
+                '(' => {
+                    if self.chars.next_if_eq(&'?').is_some()
+                        && self.chars.next_if_eq(&':').is_none()
+                    {
+                        return Err("only (?:...) groups are supported".to_owned());
+                    }
+                    let inner = self.parse_alt()?;
+                    if self.chars.next() != Some(')') {
+                        return Err("unclosed '('".to_owned());
+                    }
+                    inner
+                }
+                '[' => Node::Class(self.parse_class()?),
+                '.' => Node::Any,
+                '^' => Node::Start,
+                '$' => Node::End,
+                '\\' => self.parse_escape()?,
+                '*' | '+' | '?' => return Err(format!("nothing to repeat before '{c}'")),
+                _ => Node::Char(c),
+            };
+            nodes.push(self.parse_quantifiers(atom)?);
+        }
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.pop().unwrap(),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn parse_quantifiers(&mut self, mut atom: Node) -> Result<Node, String> {
+        loop {
+            let (min, max) = match self.chars.peek() {
+                Some('{') => match self.parse_braces() {
+                    Some(range) => range,
+                    None => return Ok(atom),
+                },
+                Some('*' | '+' | '?') => match self.chars.next() {
+                    Some('*') => (0, None),
+                    Some('+') => (1, None),
+                    _ => (0, Some(1)),
+                },
+                _ => return Ok(atom),
+            };
+            if max.is_some_and(|max| max < min) {
+                return Err(format!("invalid repetition {{{min},{}}}", max.unwrap()));
+            }
+            let greedy = self.chars.next_if_eq(&'?').is_none();
+            atom = Node::Repeat {
+                node: Box::new(atom),
+                min,
+                max,
+                greedy,
+            };
+        }
+    }
+
+    /// Parse a `{n}`, `{n,}`, or `{n,m}` quantifier. Anything else is left alone, so that the `{`
+    /// is taken literally.
+    fn parse_braces(&mut self) -> Option<(u32, Option<u32>)> {
+        let rest: String = self
+            .chars
+            .clone()
+            .skip(1)
+            .take_while(|c| *c != '}')
+            .collect();
+        let (min, max) = match rest.split_once(',') {
+            Some((min, "")) => (min.parse().ok()?, None),
+            Some((min, max)) => (min.parse().ok()?, Some(max.parse().ok()?)),
+            None => {
+                let n = rest.parse().ok()?;
+                (n, Some(n))
+            }
+        };
+        // a closing brace must follow
+        self.chars
+            .clone()
+            .nth(rest.chars().count() + 1)
+            .filter(|c| *c == '}')?;
+        for _ in 0..rest.chars().count() + 2 {
+            self.chars.next();
+        }
+        Some((min, max))
+    }
+
+    fn parse_escape(&mut self) -> Result<Node, String> {
+        let c = self.chars.next().ok_or("trailing backslash")?;
+        let class = |ranges: &[(char, char)], negated| {
+            Node::Class(Class {
+                ranges: ranges.to_vec(),
+                negated,
+            })
+        };
+        Ok(match c {
+            'd' => class(DIGIT, false),
+            'D' => class(DIGIT, true),
+            'w' => class(WORD, false),
+            'W' => class(WORD, true),
+            's' => class(SPACE, false),
+            'S' => class(SPACE, true),
+            _ => Node::Char(escaped_char(c)),
+        })
+    }
+
+    fn parse_class(&mut self) -> Result<Class, String> {
+        let negated = self.chars.next_if_eq(&'^').is_some();
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.chars.next().ok_or("unclosed '['")?;
+            let lo = match c {
+                // a ']' first in the class is a literal
+                ']' if !first => break,
+                '\\' => {
+                    let e = self.chars.next().ok_or("unclosed '['")?;
+                    match e {
+                        'd' => ranges.extend_from_slice(DIGIT),
+                        'w' => ranges.extend_from_slice(WORD),
+                        's' => ranges.extend_from_slice(SPACE),
+                        'D' | 'W' | 'S' => {
+                            return Err(format!("\\{e} isn't supported inside brackets"));
+                        }
+                        _ => (),
+                    }
+                    if "dws".contains(e) {
+                        first = false;
+                        continue;
+                    }
+                    escaped_char(e)
+                }
+                _ => c,
+            };
+            first = false;
+
+            let mut ahead = self.chars.clone();
+            if ahead.next() == Some('-') && ahead.next().is_some_and(|c| c != ']') {
+                self.chars.next();
+                let hi = match self.chars.next().unwrap() {
+                    '\\' => escaped_char(self.chars.next().ok_or("unclosed '['")?),
+                    hi => hi,
+                };
+                if hi < lo {
+                    return Err(format!("invalid range {lo}-{hi}"));
+                }
+                ranges.push((lo, hi));
+            } else {
+                ranges.push((lo, lo));
+            }
+        }
+        Ok(Class { ranges, negated })
+    }
+}
+
+fn escaped_char(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, text: &str) -> Option<(usize, usize)> {
+        Regex::new(pattern).unwrap().find_at(text, 0)
+    }
+
+    #[test]
+    fn literals_and_classes() {
+        assert_eq!(find("abc", "xxabcxx"), Some((2, 5)));
+        assert_eq!(find("a.c", "a\nc abc"), Some((4, 7)));
+        assert_eq!(find("[a-c]+", "xxbcaz"), Some((2, 5)));
+        assert_eq!(find("[^0-9]", "12a3"), Some((2, 3)));
+        assert_eq!(find("[]a]+", "x]a]"), Some((1, 4)));
+        assert_eq!(find("[a-]+", "x-a-"), Some((1, 4)));
+        assert_eq!(find(r"\d+", "cpu12"), Some((3, 5)));
+        assert_eq!(find(r"\w+", "  foo_1 "), Some((2, 7)));
+        assert_eq!(find(r"\s", "a\tb"), Some((1, 2)));
+        assert_eq!(find(r"\D\W\S", "1a!x"), Some((1, 4)));
+        assert_eq!(find(r"[\d_]+", "ab1_2c"), Some((2, 5)));
+        assert_eq!(find(r"a\.b", "axb a.b"), Some((4, 7)));
+        assert_eq!(find("é+", "xéé"), Some((1, 5)));
+        assert_eq!(find("x{", "ax{"), Some((1, 3)));
+    }
+
+    #[test]
+    fn anchors() {
+        assert_eq!(find("^a", "ba"), None);
+        assert_eq!(find("^b", "ba"), Some((0, 1)));
+        assert_eq!(find("a$", "aba"), Some((2, 3)));
+        assert_eq!(find("^$", ""), Some((0, 0)));
+        assert!(Regex::new("^cpu[0-9]+$").unwrap().is_match("cpu17"));
+        assert!(!Regex::new("^cpu[0-9]+$").unwrap().is_match("cpufreq"));
+    }
+
+    #[test]
+    fn repetition() {
+        assert_eq!(find("a*", "aaa"), Some((0, 3)));
+        assert_eq!(find("a*?", "aaa"), Some((0, 0)));
+        assert_eq!(find("a+?", "aaa"), Some((0, 1)));
+        assert_eq!(find("ba?", "baa"), Some((0, 2)));
+        assert_eq!(find("ba??", "baa"), Some((0, 1)));
+        assert_eq!(find("a{2}", "aaaa"), Some((0, 2)));
+        assert_eq!(find("a{2,}", "aaaa"), Some((0, 4)));
+        assert_eq!(find("a{2,3}", "aaaa"), Some((0, 3)));
+        assert_eq!(find("a{2,3}?", "aaaa"), Some((0, 2)));
+        assert_eq!(find("a{3}", "aa"), None);
+        assert_eq!(find("(ab)+", "xababa"), Some((1, 5)));
+        assert_eq!(find("<.+>", "<a><b>"), Some((0, 6)));
+        assert_eq!(find("<.+?>", "<a><b>"), Some((0, 3)));
+    }
+
+    #[test]
+    fn alternation_prefers_the_first() {
+        assert_eq!(find("a|ab", "ab"), Some((0, 1)));
+        assert_eq!(find("ab|a", "ab"), Some((0, 2)));
+        assert_eq!(find("(?:foo|bar)+", "xbarfoo"), Some((1, 7)));
+        assert_eq!(find("x|", "ab"), Some((0, 0)));
+        assert_eq!(find("(a|b)+c", "abab abc"), Some((5, 8)));
+    }
+
+    #[test]
+    fn find_at_offsets() {
+        let re = Regex::new("a+").unwrap();
+        assert_eq!(re.find_at("aab aa", 0), Some((0, 2)));
+        assert_eq!(re.find_at("aab aa", 1), Some((1, 2)));
+        assert_eq!(re.find_at("aab aa", 2), Some((4, 6)));
+        assert_eq!(re.find_at("aab aa", 6), None);
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        for pattern in [
+            "(a", "a)", "[a", "*a", "a{3,2}", "[z-a]", "(?=a)", "a\\", r"[\D]",
+        ] {
+            assert!(Regex::new(pattern).is_err(), "{pattern} should be invalid");
+        }
+        assert!(Regex::new("(a{1000}){1000}").is_err());
+    }
+
+    #[test]
+    fn long_lines() {
+        let line = "a".repeat(200_000);
+        assert_eq!(find("a*c", &line), None);
+        assert_eq!(find("(a|b)*$", &line), Some((0, line.len())));
+        assert_eq!(find("(a*)*b", &line), None);
+    }
+}
@@ -0,0 +1,119 @@
+//! Parsing and formatting of human-readable byte sizes
+
+const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+/// Parse a size like `4096`, `4k`, `4KiB`, or `1.5M`. Suffixes are binary multiples and
+/// case-insensitive, optionally followed by `B` or `iB`.
+pub fn parse(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (num, suffix) = s.split_at(split);
+
+    let suffix = suffix.trim_start().to_ascii_lowercase();
+    let suffix = suffix
+        .strip_suffix("ib")
+        .or_else(|| suffix.strip_suffix('b'))
+        .unwrap_or(&suffix);
+    let shift = match suffix {
+        "" => 0,
+        "k" => 10,
+        "m" => 20,
+        "g" => 30,
+        "t" => 40,
+        "p" => 50,
+        "e" => 60,
+        _ => return Err(format!("unknown size suffix in '{s}'")),
+    };
+
+    if let Ok(n) = num.parse::<u64>() {
+        n.checked_mul(1 << shift)
+            .ok_or_else(|| format!("size '{s}' is too large"))
+    } else if let Ok(n) = num.parse::<f64>() {
+        // converting to u64 saturates, so check the range first: 2^64 is exact as an f64
+        let bytes = n * (1u64 << shift) as f64;
+        if !(0.0..18446744073709551616.0).contains(&bytes) {
+            return Err(format!("size '{s}' is too large"));
+        }
+        Ok(bytes as u64)
+    } else {
+        Err(format!("invalid size '{s}'"))
+    }
+}
+
+/// Format a byte count like `512 B`, `4 KiB`, or `4.2 MiB`.
+pub fn format(bytes: u64) -> String {
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64;
+    let mut unit = UNITS[0];
+    for u in UNITS {
+        value /= 1024.0;
+        unit = u;
+        if value < 1024.0 {
+            break;
+        }
+    }
+    if value.fract() == 0.0 {
+        format!("{value} {unit}")
+    } else {
+        format!("{value:.1} {unit}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse("4096"), Ok(4096));
+        assert_eq!(parse("0"), Ok(0));
+        assert_eq!(parse("4k"), Ok(4096));
+        assert_eq!(parse("4KiB"), Ok(4096));
+        assert_eq!(parse(" 4 kb "), Ok(4096));
+        assert_eq!(parse("1M"), Ok(1 << 20));
+        assert_eq!(parse("1.5M"), Ok(3 << 19));
+        assert_eq!(parse(".5k"), Ok(512));
+        assert_eq!(parse("2g"), Ok(2 << 30));
+        assert_eq!(parse("15E"), Ok(15 << 60));
+        assert_eq!(parse("18446744073709551615"), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        for s in [
+            "", "k", ".", "1.2.3", "-1", "-1k", "1x", "nan", "inf", "1 2",
+        ] {
+            assert!(parse(s).is_err(), "{s:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn rejects_large_sizes() {
+        for s in [
+            "18446744073709551616",
+            "99999999999999999999",
+            "20000000000000000000k",
+            "16E",
+            "16.0E",
+            "17179869184G",
+            "99999999999999999999.5",
+        ] {
+            assert_eq!(parse(s), Err(format!("size '{s}' is too large")), "{s:?}");
+        }
+        assert_eq!(parse("15.5E"), Ok(31 << 59));
+    }
+
+    #[test]
+    fn formats_sizes() {
+        assert_eq!(format(0), "0 B");
+        assert_eq!(format(1023), "1023 B");
+        assert_eq!(format(1024), "1 KiB");
+        assert_eq!(format(1536), "1.5 KiB");
+        assert_eq!(format(4_400_000), "4.2 MiB");
+        assert_eq!(format(u64::MAX), "16 EiB");
+    }
+}
@@ -0,0 +1,143 @@
+//! Record templates for `--format`
+
+use std::fmt::Write;
+use std::io;
+
+use crate::output::Entry;
+use crate::text::Text;
+use crate::time;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Path,
+    Basename,
+    Content,
+    Size,
+    Mtime,
+    Error,
+    Errno,
+}
+
+const FIELDS: [(&str, Field); 7] = [
+    ("path", Field::Path),
+    ("basename", Field::Basename),
+    ("content", Field::Content),
+    ("size", Field::Size),
+    ("mtime", Field::Mtime),
+    ("error", Field::Error),
+    ("errno", Field::Errno),
+];
+
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(String),
+    Field(Field),
+}
+
+/// A parsed `--format` template, like `{path}={content}`. Braces are escaped by doubling them.
+#[derive(Debug, Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(s: &str) -> Result<Template, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.next_if_eq(&'{').is_some() => literal.push('{'),
+                '}' if chars.next_if_eq(&'}').is_some() => literal.push('}'),
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => {
+                                return Err("unclosed '{', use '{{' for a literal brace".to_owned())
+                            }
+                        }
+                    }
+                    let field = FIELDS
+                        .iter()
+                        .find(|(n, _)| *n == name)
+                        .ok_or_else(|| {
+                            let names: Vec<_> =
+                                FIELDS.iter().map(|(n, _)| format!("{{{n}}}")).collect();
+                            format!(
+                                "unknown placeholder '{{{name}}}', expected one of {}",
+                                names.join(", ")
+                            )
+                        })?
+                        .1;
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field(field));
+                }
+                '}' => return Err("unmatched '}', use '}}' for a literal brace".to_owned()),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Template { parts })
+    }
+
+    /// Whether the template is empty, so that it would render nothing at all.
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    /// Fill in the template for an entry, showing `{mtime}` in `time_style`.
+    pub fn render(&self, ent: &Entry, time_style: &time::Style) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(s) => out.push_str(s),
+                Part::Field(Field::Path) => out.push_str(&ent.label),
+                Part::Field(Field::Basename) => {
+                    out.push_str(ent.label.rsplit('/').next().unwrap_or(&ent.label))
+                }
+                Part::Field(Field::Content) => write!(out, "{}", ent.text).unwrap(),
+                Part::Field(Field::Size) => {
+                    if let Some(md) = &ent.metadata {
+                        write!(out, "{}", md.len()).unwrap();
+                    }
+                }
+                Part::Field(Field::Mtime) => {
+                    if let Some(mtime) = ent.metadata.as_ref().and_then(|md| md.modified().ok()) {
+                        out.push_str(&time::format_with(mtime, time_style));
+                    }
+                }
+                Part::Field(Field::Error) => {
+                    if let Text::Err(err) = &ent.text {
+                        out.push_str(&error_message(err));
+                    }
+                }
+                Part::Field(Field::Errno) => {
+                    if let Some(code) = ent.error_code() {
+                        write!(out, "{code}").unwrap();
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Describe an error without the `(os error 2)` that std adds to OS errors, since `{errno}` is
+/// there for those who want it.
+fn error_message(err: &io::Error) -> String {
+    let message = err.to_string();
+    match err.raw_os_error() {
+        Some(code) => message
+            .strip_suffix(&format!(" (os error {code})"))
+            .unwrap_or(&message)
+            .to_owned(),
+        None => message,
+    }
+}
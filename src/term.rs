@@ -0,0 +1,67 @@
+//! Terminal size detection
+
+use std::io::{self, IsTerminal};
+
+/// Get the width and height of the terminal on stdout, in columns and rows. `COLUMNS` and `LINES`
+/// are used when set, and otherwise the terminal is asked directly. Returns None if stdout isn't a
+/// terminal.
+pub fn size() -> Option<(usize, usize)> {
+    if !io::stdout().is_terminal() {
+        return None;
+    }
+    let env = |name| {
+        std::env::var(name)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&n| n > 0)
+    };
+    let queried = query();
+    let cols = env("COLUMNS").or(queried.map(|(cols, _)| cols))?;
+    let rows = env("LINES").or(queried.map(|(_, rows)| rows))?;
+    Some((cols, rows))
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "freebsd"
+))]
+fn query() -> Option<(usize, usize)> {
+    use std::os::raw::{c_int, c_ulong, c_ushort};
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const TIOCGWINSZ: c_ulong = 0x5413;
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    const TIOCGWINSZ: c_ulong = 0x40087468;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct WinSize {
+        ws_row: c_ushort,
+        ws_col: c_ushort,
+        ws_xpixel: c_ushort,
+        ws_ypixel: c_ushort,
+    }
+
+    extern "C" {
+        fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    }
+
+    let mut ws = WinSize::default();
+    // SAFETY: TIOCGWINSZ only writes a struct winsize through the pointer
+    if unsafe { ioctl(1, TIOCGWINSZ, &mut ws as *mut WinSize) } < 0 || ws.ws_col == 0 {
+        return None;
+    }
+    Some((ws.ws_col.into(), ws.ws_row.into()))
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "freebsd"
+)))]
+fn query() -> Option<(usize, usize)> {
+    None
+}
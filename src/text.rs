@@ -0,0 +1,602 @@
+//! Reading and classifying file contents
+
+use std::fmt::{self, Write};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::encoding::{self, Encoding};
+use crate::size;
+use crate::width;
+
+/// Maximum number of bytes read from special files with `--read-special`, unless `--max-size` is
+/// smaller
+const SPECIAL_READ_LIMIT: u64 = 64 * 1024;
+
+/// Maximum number of bytes shown in hexdumps of binary files
+const HEXDUMP_LIMIT: usize = 256;
+
+/// What to show for binary files, which contain NUL bytes or aren't valid UTF-8 without `--lossy`,
+/// from `--binary`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Binary {
+    /// Leave them out entirely
+    Skip,
+    /// Show a placeholder with the file's size
+    #[default]
+    Placeholder,
+    /// Show a hexdump of the start of the file
+    Hex,
+    /// Write the bytes out as they are
+    Raw,
+}
+
+/// What to do with Windows line endings, from `--crlf`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Crlf {
+    /// Change them to plain newlines
+    Strip,
+    /// Leave the carriage returns in the contents
+    #[default]
+    Keep,
+    /// Show the carriage returns as `^M`
+    Show,
+}
+
+/// How much whitespace to trim from contents, from `--trim`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Trim {
+    /// Only trailing newlines
+    #[default]
+    None,
+    /// All whitespace at the end
+    End,
+    /// Whitespace at the end and blank lines at the start, keeping the indentation of multiline
+    /// files' first lines
+    Both,
+    /// Like `Both`, and whitespace at the end of every line too
+    AllLines,
+}
+
+/// Settings controlling how files are read
+#[derive(Debug, Default)]
+pub struct ReadOptions {
+    /// Attempt a bounded, non-blocking read of FIFOs, sockets, and device nodes
+    pub read_special: bool,
+    /// Skip files larger than this many bytes
+    pub max_size: Option<u64>,
+    /// Read the first `max_size` bytes of large files rather than skipping them
+    pub truncate: bool,
+    pub binary: Binary,
+    /// Replace invalid UTF-8 with U+FFFD rather than treating the file as binary
+    pub lossy: bool,
+    pub encoding: Encoding,
+    pub crlf: Crlf,
+    /// Remove ANSI escape sequences, like colors, from contents
+    pub strip_ansi: bool,
+    pub trim: Trim,
+    /// Collapse runs of blank lines into one, like `cat -s`
+    pub squeeze_blank: bool,
+    /// Show control characters like `^X` and invalid bytes like `M-x`, as in `cat -v`
+    pub show_nonprinting: bool,
+    /// Also show tabs as `^I` and line ends as `$`, as in `cat -A`
+    pub show_all: bool,
+    /// Convert tabs in multiline files to spaces, with tab stops this many columns apart
+    pub expand_tabs: Option<usize>,
+}
+
+impl ReadOptions {
+    /// How many bytes to read from a source before giving up: one more than `--max-size`, so that
+    /// [`Text::from_bytes`] can tell whether there was more.
+    pub fn read_limit(&self) -> Option<u64> {
+        self.max_size.map(|max| max.saturating_add(1))
+    }
+}
+
+/// Kinds of files which aren't safe to read unconditionally
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Special {
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+}
+
+impl Special {
+    #[cfg(unix)]
+    fn from_file_type(ft: fs::FileType) -> Option<Special> {
+        use std::os::unix::fs::FileTypeExt;
+
+        if ft.is_fifo() {
+            Some(Special::Fifo)
+        } else if ft.is_socket() {
+            Some(Special::Socket)
+        } else if ft.is_block_device() {
+            Some(Special::BlockDevice)
+        } else if ft.is_char_device() {
+            Some(Special::CharDevice)
+        } else {
+            None
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn from_file_type(_ft: fs::FileType) -> Option<Special> {
+        None
+    }
+}
+
+impl fmt::Display for Special {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Special::Fifo => "fifo",
+            Special::Socket => "socket",
+            Special::BlockDevice => "block device",
+            Special::CharDevice => "character device",
+        })
+    }
+}
+
+/// Read up to `limit` bytes of a file. Special files are opened non-blocking so that a FIFO with
+/// no writer doesn't hang forever.
+fn read_bytes(path: &Path, special: bool, limit: Option<u64>) -> io::Result<Vec<u8>> {
+    let mut opts = File::options();
+    opts.read(true);
+    #[cfg(unix)]
+    if special {
+        use std::os::unix::fs::OpenOptionsExt;
+        opts.custom_flags(O_NONBLOCK);
+    }
+    #[cfg(not(unix))]
+    let _ = special;
+
+    let file = opts.open(path)?;
+    let mut buf = Vec::new();
+    match limit {
+        Some(limit) => file.take(limit).read_to_end(&mut buf)?,
+        None => (&file).read_to_end(&mut buf)?,
+    };
+    Ok(buf)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const O_NONBLOCK: i32 = 0o4000;
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+const O_NONBLOCK: i32 = 0x4;
+
+#[derive(Debug)]
+pub enum Text {
+    /// A single line of text. Will *not* contain a trailing newline
+    Oneline(String),
+    /// More than one line of text. Will contain a trailing newline
+    Multiline(String),
+    /// A symlink which wasn't followed, and the path it points to
+    Link(PathBuf),
+    /// A FIFO, socket, or device node which wasn't read
+    Special(Special),
+    /// A directory, listed with `--type d`
+    Dir,
+    /// A file larger than `--max-size` which wasn't read, and its size if known
+    TooLarge(Option<u64>),
+    /// A binary file, and how many bytes were read from it
+    Binary(u64),
+    /// A binary file to be written out as-is with `--binary raw`
+    Raw(Vec<u8>),
+    /// An error reading the file
+    Err(io::Error),
+}
+
+impl Text {
+    /// Process a text file's contents, figuring out single-line
+    fn new(mut text: String) -> Text {
+        fn count_newlines(s: &str) -> usize {
+            s.bytes().filter(|b| *b == b'\n').count()
+        }
+
+        let trimmed = text.trim_end_matches('\n');
+        let orig_count = count_newlines(&text);
+        let trimmed_count = count_newlines(trimmed);
+
+        if orig_count == 0 {
+            // no newlines in original string
+            Text::Oneline(text)
+        } else if trimmed_count == 0 {
+            // no newlines after we trim the trailing ones
+            text.truncate(trimmed.len());
+            Text::Oneline(text)
+        } else {
+            // inner newlines so this is a multi-line string. Make sure there's a trailing one
+            if !text.ends_with('\n') {
+                text.push('\n');
+            }
+            Text::Multiline(text)
+        }
+    }
+
+    /// Read the contents of a file, and strip the terminating newline if exactly one exists.
+    pub fn read(path: impl AsRef<Path>, opts: &ReadOptions) -> Text {
+        let path = path.as_ref();
+        let md = match fs::metadata(path) {
+            Ok(md) => md,
+            Err(err) => return Text::Err(err),
+        };
+
+        if md.is_dir() {
+            return Text::Dir;
+        }
+        let special = Special::from_file_type(md.file_type());
+        let limit = match special {
+            Some(kind) if !opts.read_special => return Text::Special(kind),
+            Some(_) => Some(opts.max_size.unwrap_or(u64::MAX).min(SPECIAL_READ_LIMIT)),
+            None => opts.max_size,
+        };
+        // files in /proc and /sys report a size of 0, so this can only catch some large files
+        if let Some(max) = opts.max_size {
+            if md.len() > max && !opts.truncate && special.is_none() {
+                return Text::TooLarge(Some(md.len()));
+            }
+        }
+
+        // read one byte past the limit to tell whether there was more
+        match read_bytes(path, special.is_some(), limit.map(|l| l.saturating_add(1))) {
+            Ok(buf) => {
+                Text::from_limited_bytes(buf, limit, opts.truncate || special.is_some(), opts)
+            }
+            Err(err) => Text::Err(err),
+        }
+    }
+
+    /// Process contents read from somewhere other than a local file. `buf` should have been read
+    /// with [`ReadOptions::read_limit`] so that oversized contents can be detected.
+    pub fn from_bytes(buf: Vec<u8>, opts: &ReadOptions) -> Text {
+        Text::from_limited_bytes(buf, opts.max_size, opts.truncate, opts)
+    }
+
+    fn from_limited_bytes(
+        mut buf: Vec<u8>,
+        limit: Option<u64>,
+        truncate: bool,
+        opts: &ReadOptions,
+    ) -> Text {
+        let truncated = match limit {
+            Some(limit) if buf.len() as u64 > limit => {
+                if !truncate {
+                    return Text::TooLarge(None);
+                }
+                buf.truncate(limit as usize);
+                encoding::trim_partial_char(&mut buf, opts.encoding);
+                Some(limit)
+            }
+            _ => None,
+        };
+
+        // bytes which aren't text are what --show-nonprinting is there to show
+        let text = match encoding::decode(buf, opts.encoding, opts.lossy) {
+            Ok(text) => text,
+            Err(buf) if opts.show_nonprinting => meta_notation(&buf),
+            Err(buf) => return Text::from_binary(buf, opts.binary),
+        };
+        if text.contains('\0') && !opts.show_nonprinting {
+            return Text::from_binary(text.into_bytes(), opts.binary);
+        }
+        let mut text = Text::new(clean(text, opts));
+        if let Some(limit) = truncated {
+            text.mark_truncated(limit);
+        }
+        text
+    }
+
+    fn from_binary(buf: Vec<u8>, binary: Binary) -> Text {
+        match binary {
+            Binary::Skip | Binary::Placeholder => Text::Binary(buf.len() as u64),
+            Binary::Hex => Text::new(hexdump(&buf)),
+            Binary::Raw => Text::Raw(buf),
+        }
+    }
+
+    /// Append a marker to show that a file's contents were cut off after `limit` bytes.
+    fn mark_truncated(&mut self, limit: u64) {
+        let marker = format!("[truncated at {}]", size::format(limit));
+        match self {
+            Text::Oneline(s) => {
+                s.push(' ');
+                s.push_str(&marker);
+            }
+            Text::Multiline(s) => {
+                s.push_str(&marker);
+                s.push('\n');
+            }
+            _ => (),
+        }
+    }
+
+    /// Keep only the first `n` lines of a multiline file, noting how many more there were.
+    pub fn keep_head(&mut self, n: usize) {
+        if let Text::Multiline(s) = self {
+            let total = s.lines().count();
+            if total > n {
+                let end = n.checked_sub(1).and_then(|i| s.match_indices('\n').nth(i));
+                s.truncate(end.map_or(0, |(i, _)| i + 1));
+                s.push_str(&more_lines(total - n, "more"));
+            }
+        }
+    }
+
+    /// Keep only the last `n` lines of a multiline file, noting how many came before them.
+    pub fn keep_tail(&mut self, n: usize) {
+        if let Text::Multiline(s) = self {
+            let total = s.lines().count();
+            if total > n {
+                // the nth newline from the end, not counting the trailing one, ends the skipped part
+                let start = match n.checked_sub(1) {
+                    Some(i) => s[..s.len() - 1]
+                        .rmatch_indices('\n')
+                        .nth(i)
+                        .map_or(0, |(i, _)| i + 1),
+                    None => s.len(),
+                };
+                *s = more_lines(total - n, "earlier") + &s[start..];
+            }
+        }
+    }
+
+    /// Read the target of a symlink rather than the file it points to.
+    pub fn read_link(path: impl AsRef<Path>) -> Text {
+        match fs::read_link(path) {
+            Ok(target) => Text::Link(target),
+            Err(err) => Text::Err(err),
+        }
+    }
+
+    /// Whether this goes in the multiline section of the text format. Raw binary files do too,
+    /// since they can contain anything.
+    #[inline]
+    pub fn is_multiline(&self) -> bool {
+        matches!(self, Text::Multiline(_) | Text::Raw(_))
+    }
+
+    #[inline]
+    pub fn is_err(&self) -> bool {
+        matches!(self, Text::Err(_))
+    }
+}
+
+/// Change text's contents as asked for by the read options, before it's split into lines.
+fn clean(mut text: String, opts: &ReadOptions) -> String {
+    if opts.strip_ansi && text.contains('\x1b') {
+        text = strip_ansi(&text);
+    }
+    text = match opts.crlf {
+        Crlf::Strip if text.contains('\r') => text.replace("\r\n", "\n"),
+        Crlf::Show if text.contains('\r') => text.replace("\r\n", "^M\n"),
+        _ => text,
+    };
+    text = trim(text, opts.trim);
+    if opts.squeeze_blank && (text.starts_with("\n\n") || text.contains("\n\n\n")) {
+        text = squeeze_blank(&text);
+    }
+    if let Some(stop) = opts.expand_tabs {
+        if text.contains('\t') && text.trim_end_matches('\n').contains('\n') {
+            text = expand_tabs(&text, stop);
+        }
+    }
+    if opts.show_nonprinting {
+        text = show_nonprinting(&text);
+    }
+    if opts.show_all {
+        text = text.replace('\t', "^I").replace('\n', "$\n");
+    }
+    text
+}
+
+/// Replace tabs with spaces up to the next multiple of `stop` columns, like `expand`, counting wide
+/// characters as two columns.
+fn expand_tabs(text: &str, stop: usize) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut col = 0;
+    for c in text.chars() {
+        match c {
+            '\t' => {
+                let spaces = stop - col % stop;
+                out.extend(std::iter::repeat_n(' ', spaces));
+                col += spaces;
+            }
+            '\n' => {
+                out.push(c);
+                col = 0;
+            }
+            c => {
+                out.push(c);
+                col += width::char_width(c);
+            }
+        }
+    }
+    out
+}
+
+/// Write control characters other than tabs and newlines like `cat -v`, as in `^G` and `^?`, C1
+/// controls as the `M-^X` that `cat -v` shows for their byte values, and invisible format
+/// characters like the byte order mark as `<U+FEFF>`.
+fn show_nonprinting(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\t' | '\n' => out.push(c),
+            '\0'..='\x1f' => write!(out, "^{}", char::from(c as u8 + 0x40)).unwrap(),
+            '\x7f' => out.push_str("^?"),
+            '\u{80}'..='\u{9f}' => write!(out, "M-^{}", char::from(c as u8 - 0x40)).unwrap(),
+            // soft hyphens, zero-width spaces and joiners, direction marks, and the BOM
+            '\u{ad}'
+            | '\u{200b}'..='\u{200f}'
+            | '\u{2028}'..='\u{202e}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{feff}' => write!(out, "<U+{:04X}>", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Decode UTF-8 which may contain invalid bytes, writing those like `cat -v` does, as in `M-i` for
+/// 0xe9 or `M-^@` for 0x80.
+fn meta_notation(buf: &[u8]) -> String {
+    let mut out = String::with_capacity(buf.len());
+    for chunk in buf.utf8_chunks() {
+        out.push_str(chunk.valid());
+        for &b in chunk.invalid() {
+            out.push_str("M-");
+            match b & 0x7f {
+                c @ 0..=0x1f => write!(out, "^{}", char::from(c + 0x40)).unwrap(),
+                0x7f => out.push_str("^?"),
+                c => out.push(char::from(c)),
+            }
+        }
+    }
+    out
+}
+
+fn squeeze_blank(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut blank = false;
+    for line in text.split_inclusive('\n') {
+        if line == "\n" && blank {
+            continue;
+        }
+        blank = line == "\n";
+        out.push_str(line);
+    }
+    out
+}
+
+fn trim(mut text: String, how: Trim) -> String {
+    if how == Trim::AllLines {
+        text = text
+            .lines()
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+    if how != Trim::None {
+        text.truncate(text.trim_end().len());
+    }
+    if matches!(how, Trim::Both | Trim::AllLines) {
+        // leave the first line's indentation alone unless there's only one line
+        let first = text
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or(text.len());
+        let start = text[..first].rfind('\n').map_or(0, |i| i + 1);
+        let rest = &text[start..];
+        text = if rest.contains('\n') {
+            rest
+        } else {
+            rest.trim_start()
+        }
+        .to_owned();
+    }
+    text
+}
+
+/// Remove ANSI escape sequences: CSI sequences like colors and cursor movement, OSC sequences like
+/// window titles and hyperlinks, and two-character escapes like charset selection.
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            // parameters and intermediates, up to a final byte in @ to ~
+            Some('[') => while chars.next().is_some_and(|c| !('@'..='~').contains(&c)) {},
+            // ended by BEL or ST, which is ESC \
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            // charset selection like ESC ( B takes one more character
+            Some('(' | ')' | '*' | '+') => {
+                chars.next();
+            }
+            _ => (),
+        }
+    }
+    out
+}
+
+/// Format the start of some binary data like `xxd`, with 16 bytes per line.
+fn hexdump(buf: &[u8]) -> String {
+    let mut out = String::new();
+    for (i, chunk) in buf[..buf.len().min(HEXDUMP_LIMIT)].chunks(16).enumerate() {
+        write!(out, "{:08x}:", i * 16).unwrap();
+        for (j, b) in chunk.iter().enumerate() {
+            if j % 2 == 0 {
+                out.push(' ');
+            }
+            write!(out, "{b:02x}").unwrap();
+        }
+        // line up the text column on a short last line
+        let width = chunk.len() * 2 + chunk.len().div_ceil(2);
+        write!(out, "{:pad$}  ", "", pad = 40 - width).unwrap();
+        out.extend(chunk.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        }));
+        out.push('\n');
+    }
+    if buf.len() > HEXDUMP_LIMIT {
+        let more = buf.len() - HEXDUMP_LIMIT;
+        let plural = if more == 1 { "" } else { "s" };
+        writeln!(out, "(… {more} more byte{plural})").unwrap();
+    }
+    out
+}
+
+/// The line marking where lines were left out of a multiline file.
+fn more_lines(count: usize, which: &str) -> String {
+    let plural = if count == 1 { "" } else { "s" };
+    format!("(… {count} {which} line{plural})\n")
+}
+
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Text::Oneline(s) | Text::Multiline(s) => f.pad(s),
+            Text::Link(target) => write!(f, "-> {}", target.display()),
+            Text::Special(special) => write!(f, "[{special}]"),
+            Text::Dir => f.write_str("[directory]"),
+            Text::TooLarge(Some(len)) => write!(f, "[skipped: {}]", size::format(*len)),
+            Text::TooLarge(None) => f.write_str("[skipped: too large]"),
+            Text::Binary(len) => write!(f, "[binary, {}]", size::format(*len)),
+            Text::Raw(data) => f.write_str(&String::from_utf8_lossy(data)),
+            Text::Err(err) => write!(f, "[Error: {err}]"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cleaned(text: &str, opts: ReadOptions) -> String {
+        clean(text.to_owned(), &opts)
+    }
+
+    #[test]
+    fn squeeze_blank_lines() {
+        let opts = || ReadOptions {
+            squeeze_blank: true,
+            ..Default::default()
+        };
+        assert_eq!(cleaned("\n\nx\n", opts()), "\nx\n");
+        assert_eq!(cleaned("a\n\n\n\nb\n\n\n", opts()), "a\n\nb\n\n");
+        assert_eq!(cleaned("a\n\nb\n", opts()), "a\n\nb\n");
+        assert_eq!(cleaned("\n", opts()), "\n");
+    }
+}
@@ -0,0 +1,491 @@
+//! Parsing and formatting durations and timestamps
+
+use std::fmt::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Parse a duration like `90`, `90s`, `15m`, `1h30m`, `2d`, or `1w`. A bare number is seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let err = || format!("invalid duration '{s}'");
+    if s.is_empty() {
+        return Err(err());
+    }
+    if let Ok(secs) = s.parse::<f64>() {
+        return Duration::try_from_secs_f64(secs).map_err(|_| err());
+    }
+
+    let mut total = 0.0;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .ok_or_else(err)?;
+        let num: f64 = rest[..split].parse().map_err(|_| err())?;
+        let unit_len = rest[split..]
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len() - split);
+        let scale = match &rest[split..split + unit_len] {
+            "s" | "sec" | "secs" => 1.0,
+            "m" | "min" | "mins" => 60.0,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 3600.0,
+            "d" | "day" | "days" => 86400.0,
+            "w" | "week" | "weeks" => 7.0 * 86400.0,
+            _ => return Err(err()),
+        };
+        total += num * scale;
+        rest = &rest[split + unit_len..];
+    }
+    Duration::try_from_secs_f64(total).map_err(|_| err())
+}
+
+/// Parse a point in time: a duration ago (like `1h`), `@SECONDS` since the epoch, or an ISO 8601
+/// date or date and time. Times without a `Z` or `+HH:MM` offset are in the local time zone.
+pub fn parse_time(s: &str) -> Result<SystemTime, String> {
+    if let Ok(ago) = parse_duration(s) {
+        return SystemTime::now()
+            .checked_sub(ago)
+            .ok_or_else(|| format!("duration '{s}' is too long"));
+    }
+    if let Some(epoch) = s.strip_prefix('@') {
+        let secs: i64 = epoch
+            .parse()
+            .map_err(|_| format!("invalid timestamp '{s}'"))?;
+        return Ok(from_unix(secs));
+    }
+    parse_iso(s).map(from_unix).ok_or_else(|| {
+        format!("invalid time '{s}', expected a duration, @SECONDS, or YYYY-MM-DD[THH:MM[:SS]]")
+    })
+}
+
+fn from_unix(secs: i64) -> SystemTime {
+    if secs >= 0 {
+        UNIX_EPOCH + Duration::from_secs(secs as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs())
+    }
+}
+
+/// Seconds since the epoch for a [`SystemTime`], which may be negative.
+pub fn to_unix(t: SystemTime) -> i64 {
+    match t.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(err) => -(err.duration().as_secs_f64().ceil() as i64),
+    }
+}
+
+/// How to show timestamps, from `--time-style`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum Style {
+    /// `2024-01-31 13:45:00`
+    #[default]
+    Default,
+    /// `2024-01-31T13:45:00+01:00`
+    Iso,
+    /// `3m ago`
+    Relative,
+    /// Seconds since the epoch
+    Epoch,
+    /// A `strftime` pattern, checked by [`parse_style`]
+    Format(String),
+}
+
+const FORMAT_CHARS: &str = "YmdHMSyjeIpabABFTzsn%t";
+
+/// Parse a `--time-style`: `default`, `iso`, `relative`, `epoch`, or a `strftime` pattern like
+/// `+%H:%M`, where the `+` is optional if it has a `%`.
+pub fn parse_style(s: &str) -> Result<Style, String> {
+    let pattern = match s {
+        "default" => return Ok(Style::Default),
+        "iso" => return Ok(Style::Iso),
+        "relative" => return Ok(Style::Relative),
+        "epoch" => return Ok(Style::Epoch),
+        _ => match s.strip_prefix('+') {
+            Some(pattern) => pattern,
+            None if s.contains('%') => s,
+            None => {
+                return Err(format!(
+                    "invalid time style '{s}', expected default, iso, relative, epoch, or +FORMAT"
+                ))
+            }
+        },
+    };
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c == '%' {
+            match chars.next() {
+                Some(c) if FORMAT_CHARS.contains(c) => (),
+                Some(c) => return Err(format!("unsupported time format '%{c}'")),
+                None => {
+                    return Err("time format ends with '%', use '%%' for a literal one".to_owned())
+                }
+            }
+        }
+    }
+    Ok(Style::Format(pattern.to_owned()))
+}
+
+/// Format a time in a style, in the local time zone.
+pub fn format_with(t: SystemTime, style: &Style) -> String {
+    let secs = to_unix(t);
+    let offset = local_offset(secs);
+    match style {
+        Style::Default => strftime(secs, offset, "%F %T"),
+        Style::Iso => strftime(secs, offset, "%FT%T%z"),
+        Style::Relative => relative(secs, to_unix(SystemTime::now())),
+        Style::Epoch => secs.to_string(),
+        Style::Format(pattern) => strftime(secs, offset, pattern),
+    }
+}
+
+fn relative(secs: i64, now: i64) -> String {
+    let ago = now - secs;
+    let n = ago.unsigned_abs();
+    let amount = match n {
+        0..=59 => format!("{n}s"),
+        60..=3599 => format!("{}m", n / 60),
+        3600..=86399 => format!("{}h", n / 3600),
+        _ => format!("{}d", n / 86400),
+    };
+    if ago < 0 {
+        format!("in {amount}")
+    } else {
+        format!("{amount} ago")
+    }
+}
+
+/// Format a Unix time with a UTC offset like `strftime`, supporting the conversions in
+/// [`FORMAT_CHARS`]. `%z` is written with a colon, like `+01:00`, as ISO 8601 prefers.
+fn strftime(secs: i64, offset: i64, pattern: &str) -> String {
+    const DAYS: [&str; 7] = [
+        "Sunday",
+        "Monday",
+        "Tuesday",
+        "Wednesday",
+        "Thursday",
+        "Friday",
+        "Saturday",
+    ];
+    const MONTHS: [&str; 12] = [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December",
+    ];
+
+    let local = secs + offset;
+    let days = local.div_euclid(86400);
+    let (year, month, day) = civil_from_days(days);
+    let tod = local.rem_euclid(86400);
+    let (hour, min, sec) = (tod / 3600, tod / 60 % 60, tod % 60);
+    // 1970-01-01 was a Thursday
+    let weekday = DAYS[(days + 4).rem_euclid(7) as usize];
+    let month_name = MONTHS[month as usize - 1];
+
+    let mut out = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let _ = match chars.next() {
+            Some('Y') => write!(out, "{year:04}"),
+            Some('m') => write!(out, "{month:02}"),
+            Some('d') => write!(out, "{day:02}"),
+            Some('e') => write!(out, "{day:2}"),
+            Some('H') => write!(out, "{hour:02}"),
+            Some('I') => write!(out, "{:02}", (hour + 11) % 12 + 1),
+            Some('p') => write!(out, "{}", if hour < 12 { "AM" } else { "PM" }),
+            Some('M') => write!(out, "{min:02}"),
+            Some('S') => write!(out, "{sec:02}"),
+            Some('y') => write!(out, "{:02}", year.rem_euclid(100)),
+            Some('j') => write!(out, "{:03}", days - days_from_civil(year, 1, 1) + 1),
+            Some('a') => write!(out, "{}", &weekday[..3]),
+            Some('A') => write!(out, "{weekday}"),
+            Some('b') => write!(out, "{}", &month_name[..3]),
+            Some('B') => write!(out, "{month_name}"),
+            Some('F') => write!(out, "{year:04}-{month:02}-{day:02}"),
+            Some('T') => write!(out, "{hour:02}:{min:02}:{sec:02}"),
+            Some('z') => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                let off = offset.unsigned_abs();
+                write!(out, "{sign}{:02}:{:02}", off / 3600, off / 60 % 60)
+            }
+            Some('s') => write!(out, "{secs}"),
+            Some('n') => out.write_char('\n'),
+            Some('t') => out.write_char('\t'),
+            Some('%') => out.write_char('%'),
+            Some(c) => write!(out, "%{c}"),
+            None => out.write_char('%'),
+        };
+    }
+    out
+}
+
+fn parse_iso(s: &str) -> Option<i64> {
+    fn num(s: &str, len: usize) -> Option<(i64, &str)> {
+        let digits = s.get(..len)?;
+        digits.bytes().all(|b| b.is_ascii_digit()).then_some(())?;
+        Some((digits.parse().ok()?, &s[len..]))
+    }
+
+    let (year, s) = num(s, 4)?;
+    let (month, s) = num(s.strip_prefix('-')?, 2)?;
+    let (day, mut s) = num(s.strip_prefix('-')?, 2)?;
+    if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return None;
+    }
+
+    let mut secs = 0;
+    if let Some(rest) = s.strip_prefix(['T', ' ']) {
+        let (hour, rest) = num(rest, 2)?;
+        let (min, rest) = num(rest.strip_prefix(':')?, 2)?;
+        let (sec, rest) = match rest.strip_prefix(':') {
+            Some(rest) => num(rest, 2)?,
+            None => (0, rest),
+        };
+        if hour > 23 || min > 59 || sec > 60 {
+            return None;
+        }
+        secs = hour * 3600 + min * 60 + sec;
+        s = rest;
+    }
+
+    let local = days_from_civil(year, month, day) * 86400 + secs;
+    let offset = match s {
+        "" => return Some(local - local_offset_at(local)),
+        "Z" | "z" => 0,
+        _ => {
+            let (sign, rest) = match s.as_bytes()[0] {
+                b'+' => (1, &s[1..]),
+                b'-' => (-1, &s[1..]),
+                _ => return None,
+            };
+            let (hours, rest) = num(rest, 2)?;
+            let (mins, rest) = num(rest.strip_prefix(':').unwrap_or(rest), 2)?;
+            if !rest.is_empty() || hours > 23 || mins > 59 {
+                return None;
+            }
+            sign * (hours * 3600 + mins * 60)
+        }
+    };
+    Some(local - offset)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian calendar date.
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // from Howard Hinnant's date algorithms
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// The calendar date `(year, month, day)` for a number of days since 1970-01-01.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // the inverse of days_from_civil
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Find the UTC offset for a local time, given as seconds since the epoch as if it were UTC.
+fn local_offset_at(local: i64) -> i64 {
+    // the offset at the true instant can differ around DST changes, so refine the guess once
+    let guess = local_offset(local);
+    local_offset(local - guess)
+}
+
+/// The local time zone's offset from UTC in seconds at a Unix time. time_t is only sure to be 64
+/// bits on 64-bit targets, so the rest show times in UTC.
+#[cfg(all(unix, target_pointer_width = "64"))]
+pub fn local_offset(t: i64) -> i64 {
+    use std::os::raw::{c_char, c_int, c_long};
+
+    #[repr(C)]
+    struct Tm {
+        tm_sec: c_int,
+        tm_min: c_int,
+        tm_hour: c_int,
+        tm_mday: c_int,
+        tm_mon: c_int,
+        tm_year: c_int,
+        tm_wday: c_int,
+        tm_yday: c_int,
+        tm_isdst: c_int,
+        tm_gmtoff: c_long,
+        tm_zone: *const c_char,
+    }
+
+    extern "C" {
+        fn localtime_r(t: *const i64, tm: *mut Tm) -> *mut Tm;
+    }
+
+    let mut tm = std::mem::MaybeUninit::<Tm>::zeroed();
+    // SAFETY: both pointers are valid, and localtime_r fills in tm when it returns non-null
+    let res = unsafe { localtime_r(&t, tm.as_mut_ptr()) };
+    if res.is_null() {
+        0
+    } else {
+        unsafe { tm.assume_init() }.tm_gmtoff
+    }
+}
+
+#[cfg(not(all(unix, target_pointer_width = "64")))]
+pub fn local_offset(_t: i64) -> i64 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        let secs = |s| parse_duration(s).map(|d| d.as_secs_f64());
+        assert_eq!(secs("90"), Ok(90.0));
+        assert_eq!(secs("1.5"), Ok(1.5));
+        assert_eq!(secs("90s"), Ok(90.0));
+        assert_eq!(secs("15m"), Ok(900.0));
+        assert_eq!(secs("1h30m"), Ok(5400.0));
+        assert_eq!(secs("2days"), Ok(172800.0));
+        assert_eq!(secs("1w"), Ok(604800.0));
+        assert_eq!(secs("0.5h"), Ok(1800.0));
+        for bad in ["", "m", "5x", "1h30", "-5", "inf", "NaN", "1..5m", "h1"] {
+            assert!(parse_duration(bad).is_err(), "{bad} should be invalid");
+        }
+    }
+
+    #[test]
+    fn iso_dates() {
+        assert_eq!(parse_iso("1970-01-01Z"), Some(0));
+        assert_eq!(parse_iso("1970-01-02T00:00Z"), Some(86400));
+        assert_eq!(parse_iso("2024-02-29T12:34:56Z"), Some(1709210096));
+        assert_eq!(
+            parse_iso("2024-02-29 12:34:56+02:00"),
+            Some(1709210096 - 7200)
+        );
+        assert_eq!(
+            parse_iso("2024-02-29T12:34:56-0130"),
+            Some(1709210096 + 5400)
+        );
+        assert_eq!(parse_iso("1969-12-31T23:59:59Z"), Some(-1));
+    }
+
+    #[test]
+    fn invalid_dates() {
+        for bad in [
+            "2024-02-30Z",
+            "2024-02-31Z",
+            "2023-02-29Z",
+            "1900-02-29Z",
+            "2024-04-31Z",
+            "2024-13-01Z",
+            "2024-00-10Z",
+            "2024-01-00Z",
+            "2024-1-01Z",
+            "2024-01-01T24:00Z",
+            "2024-01-01T12:60Z",
+            "2024-01-01T12Z",
+            "2024-01-01T12:00+24:00",
+            "2024-01-01T12:00+01:00x",
+            "2024-01-01X",
+            "20240101",
+        ] {
+            assert_eq!(parse_iso(bad), None, "{bad} should be invalid");
+        }
+        assert!(parse_iso("2000-02-29Z").is_some());
+        assert!(parse_time("2024-02-31").is_err());
+    }
+
+    #[test]
+    fn points_in_time() {
+        assert_eq!(parse_time("@0"), Ok(UNIX_EPOCH));
+        assert_eq!(to_unix(parse_time("@-86400").unwrap()), -86400);
+        assert!(parse_time("@soon").is_err());
+        let ago = parse_time("1h").unwrap();
+        let diff = to_unix(SystemTime::now()) - to_unix(ago);
+        assert!((3599..=3601).contains(&diff));
+    }
+
+    #[test]
+    fn styles() {
+        assert_eq!(parse_style("iso"), Ok(Style::Iso));
+        assert_eq!(parse_style("epoch"), Ok(Style::Epoch));
+        assert_eq!(parse_style("+%H:%M"), Ok(Style::Format("%H:%M".to_owned())));
+        assert_eq!(parse_style("%Y"), Ok(Style::Format("%Y".to_owned())));
+        assert_eq!(parse_style("+plain"), Ok(Style::Format("plain".to_owned())));
+        assert!(parse_style("fancy").is_err());
+        assert!(parse_style("+%Q").is_err());
+        assert!(parse_style("+100%").is_err());
+        assert!(parse_style("+100%%").is_ok());
+    }
+
+    #[test]
+    fn formats() {
+        // 2024-02-29 13:04:05 UTC, a Thursday
+        let t = 1709211845;
+        assert_eq!(strftime(t, 0, "%F %T"), "2024-02-29 13:04:05");
+        assert_eq!(strftime(t, 0, "%Y/%m/%d %H.%M.%S"), "2024/02/29 13.04.05");
+        assert_eq!(strftime(t, 0, "%y %j %e"), "24 060 29");
+        assert_eq!(
+            strftime(t, 0, "%I%p %a %A %b %B"),
+            "01PM Thu Thursday Feb February"
+        );
+        assert_eq!(strftime(t, 0, "%s%%%n%t"), "1709211845%\n\t");
+        assert_eq!(strftime(t, 3600, "%T%z"), "14:04:05+01:00");
+        assert_eq!(strftime(t, -16200, "%T%z"), "08:34:05-04:30");
+        // the offset can move the date
+        assert_eq!(strftime(t, 12 * 3600, "%F %a"), "2024-03-01 Fri");
+        assert_eq!(strftime(0, 0, "%I %p"), "12 AM");
+        assert_eq!(strftime(-1, 0, "%F %T"), "1969-12-31 23:59:59");
+        assert_eq!(strftime(t, 0, "100%"), "100%");
+    }
+
+    #[test]
+    fn relative_times() {
+        assert_eq!(relative(100, 100), "0s ago");
+        assert_eq!(relative(100, 159), "59s ago");
+        assert_eq!(relative(0, 3599), "59m ago");
+        assert_eq!(relative(0, 7200), "2h ago");
+        assert_eq!(relative(0, 86400 * 3), "3d ago");
+        assert_eq!(relative(120, 0), "in 2m");
+    }
+
+    #[test]
+    fn civil_round_trip() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        for days in (-800_000..800_000).step_by(997) {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m.into(), d.into()), days);
+        }
+    }
+}
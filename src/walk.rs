@@ -0,0 +1,338 @@
+//! Directory traversal for `--all` and `--recursive`
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::glob;
+use crate::ignore::Gitignore;
+use crate::regex::Regex;
+
+/// How symlinks are treated
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Symlinks {
+    /// Skip symlinks found while walking directories, but follow them when named explicitly
+    #[default]
+    Skip,
+    /// Always dereference symlinks
+    Follow,
+    /// Never dereference symlinks, list them so that their target is shown instead
+    Show,
+}
+
+/// Kinds of directory entries, for `--type`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    File,
+    Symlink,
+    Dir,
+    Fifo,
+    Socket,
+    CharDevice,
+    BlockDevice,
+}
+
+impl FileType {
+    fn of(ft: fs::FileType) -> Option<FileType> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileTypeExt;
+            if ft.is_fifo() {
+                return Some(FileType::Fifo);
+            } else if ft.is_socket() {
+                return Some(FileType::Socket);
+            } else if ft.is_char_device() {
+                return Some(FileType::CharDevice);
+            } else if ft.is_block_device() {
+                return Some(FileType::BlockDevice);
+            }
+        }
+        if ft.is_file() {
+            Some(FileType::File)
+        } else if ft.is_symlink() {
+            Some(FileType::Symlink)
+        } else if ft.is_dir() {
+            Some(FileType::Dir)
+        } else {
+            None
+        }
+    }
+}
+
+/// Order of listed files
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Sort {
+    /// Sort by path
+    #[default]
+    Name,
+    /// Largest files first
+    Size,
+    /// Most recently modified files first
+    Mtime,
+    /// Keep the order returned by the filesystem
+    None,
+}
+
+/// Settings controlling which directory entries are listed
+#[derive(Debug, Default)]
+pub struct Options {
+    /// Descend into subdirectories
+    pub recursive: bool,
+    /// When recursing, only list files at most this many levels below the starting directory,
+    /// where 1 is the starting directory's own files
+    pub max_depth: Option<usize>,
+    /// Glob patterns of paths to skip. Patterns without a `/` match against the filename, and
+    /// patterns with one match against the whole path relative to the starting directory.
+    pub exclude: Vec<String>,
+    /// Skip files and directories whose names start with `.`
+    pub skip_hidden: bool,
+    /// Only list files with one of these extensions, if non-empty
+    pub extensions: Vec<String>,
+    /// Only list files whose names match one of these patterns, if non-empty
+    pub names: Vec<Regex>,
+    /// Kinds of entries to list. If empty, regular files and any symlinks which aren't skipped.
+    pub types: Vec<FileType>,
+    /// Skip paths ignored by git
+    pub gitignore: bool,
+    pub symlinks: Symlinks,
+    pub sort: Sort,
+    /// Reverse the sort order
+    pub reverse: bool,
+    /// Only list files modified after this time
+    pub newer: Option<SystemTime>,
+    /// Only list files modified before this time
+    pub older: Option<SystemTime>,
+    /// Only list files of at least this many bytes
+    pub min_size: Option<u64>,
+    /// Only list files of at most this many bytes
+    pub max_size: Option<u64>,
+}
+
+impl Options {
+    fn matches_type(&self, ft: fs::FileType) -> bool {
+        if self.types.is_empty() {
+            ft.is_file() || ft.is_symlink()
+        } else {
+            FileType::of(ft).is_some_and(|kind| self.types.contains(&kind))
+        }
+    }
+
+    fn matches_name(&self, name: &str) -> bool {
+        self.names.is_empty() || self.names.iter().any(|re| re.is_match(name))
+    }
+
+    fn has_extension(&self, name: &str) -> bool {
+        self.extensions.is_empty()
+            || self.extensions.iter().any(|ext| {
+                name.strip_suffix(ext.as_str())
+                    .is_some_and(|stem| stem.len() > 1 && stem.ends_with('.'))
+            })
+    }
+
+    /// Whether a file passes the filters which need its metadata.
+    fn matches_metadata(&self, path: &Path, md: Option<&fs::Metadata>) -> bool {
+        let check_time = self.newer.is_some() || self.older.is_some();
+        let check_size = self.min_size.is_some() || self.max_size.is_some();
+        if !check_time && !check_size {
+            return true;
+        }
+        // files whose metadata can't be read can't match a filter
+        let Some(md) = md else {
+            return false;
+        };
+        if check_time {
+            let Ok(mtime) = md.modified() else {
+                return false;
+            };
+            if self.newer.is_some_and(|t| mtime <= t) || self.older.is_some_and(|t| mtime >= t) {
+                return false;
+            }
+        }
+        if check_size {
+            let Some(size) = self.file_size(path, md) else {
+                return false;
+            };
+            if self.min_size.is_some_and(|min| size < min)
+                || self.max_size.is_some_and(|max| size > max)
+            {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Get a file's size for the size filters. Files in /proc and similar filesystems report a size
+    /// of 0, so empty-looking files are read, up to just past the limits, to find their real size.
+    fn file_size(&self, path: &Path, md: &fs::Metadata) -> Option<u64> {
+        if md.len() > 0 || !md.is_file() {
+            return Some(md.len());
+        }
+        let bound = self
+            .max_size
+            .map_or(0, |max| max + 1)
+            .max(self.min_size.unwrap_or(0));
+        let mut buf = Vec::new();
+        fs::File::open(path)
+            .ok()?
+            .take(bound)
+            .read_to_end(&mut buf)
+            .ok()?;
+        Some(buf.len() as u64)
+    }
+
+    fn is_excluded(&self, path: &str) -> bool {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        self.exclude.iter().any(|pat| {
+            if pat.contains('/') {
+                glob::matches_path(pat, path, true)
+            } else {
+                glob::matches(pat, name)
+            }
+        })
+    }
+}
+
+/// List the regular files in `root`, descending into subdirectories if requested.
+///
+/// Returned paths are relative to `root`. Failing to read `root` itself is an error, while
+/// unreadable subdirectories are reported on stderr and skipped.
+pub fn list_files(root: &Path, opts: &Options) -> io::Result<Vec<String>> {
+    let mut walker = Walker {
+        root,
+        opts,
+        ignore: opts.gitignore.then(|| Gitignore::new(root)).flatten(),
+        ancestors: Vec::new(),
+        files: Vec::new(),
+    };
+    walker.walk_dir(&PathBuf::new())?;
+    let mut files = walker.files;
+    sort_files(root, &mut files, opts.sort);
+    if opts.reverse {
+        files.reverse();
+    }
+    Ok(files)
+}
+
+fn sort_files(root: &Path, files: &mut [String], sort: Sort) {
+    let metadata = |path: &String| {
+        let path = root.join(path);
+        fs::metadata(&path)
+            .or_else(|_| fs::symlink_metadata(&path))
+            .ok()
+    };
+    match sort {
+        Sort::Name => files.sort(),
+        // files whose metadata can't be read sort last
+        Sort::Size => {
+            files.sort_by_cached_key(|path| std::cmp::Reverse(metadata(path).map(|md| md.len())))
+        }
+        Sort::Mtime => files.sort_by_cached_key(|path| {
+            std::cmp::Reverse(metadata(path).and_then(|md| md.modified().ok()))
+        }),
+        Sort::None => (),
+    }
+}
+
+struct Walker<'a> {
+    root: &'a Path,
+    opts: &'a Options,
+    ignore: Option<Gitignore>,
+    /// Canonical paths of the directories being walked, to detect symlink loops
+    ancestors: Vec<PathBuf>,
+    files: Vec<String>,
+}
+
+impl Walker<'_> {
+    fn walk_dir(&mut self, rel: &Path) -> io::Result<()> {
+        let dir = self.root.join(rel);
+        let entries = fs::read_dir(&dir)?;
+        // anything which can fail comes before the ignore rules are pushed, so they're always
+        // popped again
+        if self.opts.symlinks == Symlinks::Follow {
+            self.ancestors.push(fs::canonicalize(&dir)?);
+        }
+        // entries with non-UTF-8 names are never descended into, so this is never lossy
+        let rel_str = rel.to_string_lossy();
+        let marker = self.ignore.as_mut().map(|ign| ign.push(&dir, &rel_str));
+
+        for ent in entries.filter_map(|res| res.ok()) {
+            let Ok(mut ft) = ent.file_type() else {
+                continue;
+            };
+            if ft.is_symlink() {
+                match self.opts.symlinks {
+                    // asking for symlinks by type overrides skipping them
+                    Symlinks::Skip if !self.opts.types.contains(&FileType::Symlink) => continue,
+                    Symlinks::Skip => (),
+                    Symlinks::Show => (),
+                    // list broken links as-is so that the read error shows up in the output
+                    Symlinks::Follow => {
+                        ft = fs::metadata(ent.path()).map_or(ft, |md| md.file_type())
+                    }
+                }
+            }
+            let path = rel.join(ent.file_name());
+            let Some(path_str) = path.to_str() else {
+                // labels are strings, so there's no way to show these
+                eprintln!(
+                    "tabby: skipping {}: filename is not UTF-8",
+                    self.root.join(&path).display()
+                );
+                continue;
+            };
+            if (self.opts.skip_hidden && ent.file_name().to_string_lossy().starts_with('.'))
+                || self.opts.is_excluded(path_str)
+                || self
+                    .ignore
+                    .as_ref()
+                    .is_some_and(|ign| ign.is_ignored(path_str, ft.is_dir()))
+            {
+                continue;
+            }
+
+            if self.opts.matches_type(ft) {
+                let md = match self.opts.symlinks {
+                    Symlinks::Follow => fs::metadata(ent.path()).ok(),
+                    _ => ent.metadata().ok(),
+                };
+                let name = ent.file_name().to_string_lossy().into_owned();
+                if self.opts.has_extension(&name)
+                    && self.opts.matches_name(&name)
+                    && self
+                        .opts
+                        .matches_metadata(&self.root.join(&path), md.as_ref())
+                {
+                    self.files.push(path_str.to_owned());
+                }
+            }
+            if ft.is_dir() && self.should_descend(rel) {
+                if self.is_loop(&ent.path()) {
+                    eprintln!("tabby: skipping symlink loop at {}", path.display());
+                } else if let Err(err) = self.walk_dir(&path) {
+                    eprintln!("tabby: unable to read directory {}: {err}", path.display());
+                }
+            }
+        }
+
+        if self.opts.symlinks == Symlinks::Follow {
+            self.ancestors.pop();
+        }
+        if let (Some(ign), Some(marker)) = (self.ignore.as_mut(), marker) {
+            ign.pop(marker);
+        }
+        Ok(())
+    }
+
+    /// Whether to descend into a subdirectory of `rel`.
+    fn should_descend(&self, rel: &Path) -> bool {
+        // files in rel's subdirectory are this deep, relative to the root
+        let depth = rel.components().count() + 2;
+        self.opts.recursive && self.opts.max_depth.is_none_or(|max| depth <= max)
+    }
+
+    fn is_loop(&self, dir: &Path) -> bool {
+        self.opts.symlinks == Symlinks::Follow
+            && fs::canonicalize(dir).is_ok_and(|dir| self.ancestors.contains(&dir))
+    }
+}
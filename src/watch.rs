@@ -0,0 +1,169 @@
+//! Waiting for changes to files for `--watch`
+//!
+//! On Linux, changes are detected with inotify. Files in /proc and /sys mostly don't generate
+//! inotify events though, so callers also wake up periodically to poll for changes.
+
+use std::path::Path;
+use std::time::Duration;
+
+#[cfg(target_os = "linux")]
+use inotify::Inotify as Backend;
+
+/// Elsewhere there's no backend, so we only ever poll
+#[cfg(not(target_os = "linux"))]
+enum Backend {}
+
+#[cfg(not(target_os = "linux"))]
+impl Backend {
+    fn new() -> Option<Backend> {
+        None
+    }
+    fn add(&mut self, _path: &Path) {
+        match *self {}
+    }
+    fn wait(&mut self, _timeout: Duration) -> bool {
+        match *self {}
+    }
+}
+
+pub struct Watcher {
+    backend: Option<Backend>,
+    paths: Vec<Box<Path>>,
+}
+
+impl Watcher {
+    /// Watch the given files, and the directories containing them so that files which are
+    /// replaced by a rename are noticed.
+    pub fn new<'a>(paths: impl IntoIterator<Item = &'a Path>) -> Watcher {
+        let mut w = Watcher {
+            backend: Backend::new(),
+            paths: paths.into_iter().map(Box::from).collect(),
+        };
+        w.add_watches();
+        w
+    }
+
+    fn add_watches(&mut self) {
+        if let Some(backend) = self.backend.as_mut() {
+            for path in &self.paths {
+                backend.add(path);
+                if let Some(parent) = path.parent() {
+                    backend.add(if parent.as_os_str().is_empty() {
+                        Path::new(".")
+                    } else {
+                        parent
+                    });
+                }
+            }
+        }
+    }
+
+    /// Wait until a watched file changes or `timeout` passes. Returns whether a change was seen.
+    pub fn wait(&mut self, timeout: Duration) -> bool {
+        match self.backend.as_mut() {
+            Some(backend) => {
+                let changed = backend.wait(timeout);
+                if changed {
+                    // re-add watches in case files were replaced rather than modified in place
+                    self.add_watches();
+                }
+                changed
+            }
+            None => {
+                std::thread::sleep(timeout);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod inotify {
+    use std::ffi::CString;
+    use std::fs::File;
+    use std::io::Read;
+    use std::os::raw::{c_char, c_int, c_short, c_ulong};
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+    use std::path::Path;
+    use std::time::Duration;
+
+    const IN_NONBLOCK: c_int = 0o4000;
+    const IN_CLOEXEC: c_int = 0o2000000;
+
+    const IN_MODIFY: u32 = 0x2;
+    const IN_ATTRIB: u32 = 0x4;
+    const IN_CLOSE_WRITE: u32 = 0x8;
+    const IN_MOVED_TO: u32 = 0x80;
+    const IN_CREATE: u32 = 0x100;
+    const IN_DELETE_SELF: u32 = 0x400;
+    const IN_MOVE_SELF: u32 = 0x800;
+    const WATCH_MASK: u32 = IN_MODIFY
+        | IN_ATTRIB
+        | IN_CLOSE_WRITE
+        | IN_MOVED_TO
+        | IN_CREATE
+        | IN_DELETE_SELF
+        | IN_MOVE_SELF;
+
+    const POLLIN: c_short = 0x1;
+
+    #[repr(C)]
+    struct PollFd {
+        fd: c_int,
+        events: c_short,
+        revents: c_short,
+    }
+
+    extern "C" {
+        fn inotify_init1(flags: c_int) -> c_int;
+        fn inotify_add_watch(fd: c_int, pathname: *const c_char, mask: u32) -> c_int;
+        fn poll(fds: *mut PollFd, nfds: c_ulong, timeout: c_int) -> c_int;
+    }
+
+    pub struct Inotify {
+        file: File,
+    }
+
+    impl Inotify {
+        pub fn new() -> Option<Inotify> {
+            // SAFETY: inotify_init1 has no memory safety requirements, and the returned fd is
+            // owned by the File from here on
+            let fd = unsafe { inotify_init1(IN_NONBLOCK | IN_CLOEXEC) };
+            (fd >= 0).then(|| Inotify {
+                file: unsafe { File::from_raw_fd(fd) },
+            })
+        }
+
+        pub fn add(&mut self, path: &Path) {
+            if let Ok(cpath) = CString::new(path.as_os_str().as_bytes()) {
+                // SAFETY: cpath is a valid NUL-terminated string. Failures (like a file which
+                // doesn't exist yet) are fine to ignore.
+                unsafe { inotify_add_watch(self.file.as_raw_fd(), cpath.as_ptr(), WATCH_MASK) };
+            }
+        }
+
+        /// Discard all pending events.
+        fn drain(&mut self) {
+            let mut buf = [0u8; 4096];
+            while matches!(self.file.read(&mut buf), Ok(n) if n > 0) {}
+        }
+
+        pub fn wait(&mut self, timeout: Duration) -> bool {
+            let mut pfd = PollFd {
+                fd: self.file.as_raw_fd(),
+                events: POLLIN,
+                revents: 0,
+            };
+            let ms = timeout.as_millis().min(c_int::MAX as u128) as c_int;
+            // SAFETY: pfd is a single valid pollfd struct
+            let ready = unsafe { poll(&mut pfd, 1, ms) } > 0;
+            if ready {
+                // changes often come in bursts, so let them settle before reporting one
+                std::thread::sleep(Duration::from_millis(50));
+                self.drain();
+            }
+            ready
+        }
+    }
+}